@@ -42,17 +42,11 @@ impl SimEnv {
                     f.fn_id,
                     ContainerMetric {
                         container_count: self.fn_container_cnt(f.fn_id),
-                        scheduled_fn_count: self.core.fn_2_nodes().get(&f.fn_id).map_or_else(
-                            || 0,
-                            |nodes| {
-                                nodes
-                                    .iter()
-                                    .map(|n| {
-                                        self.node(*n).container(f.fn_id).unwrap().req_fn_state.len()
-                                    })
-                                    .sum()
-                            },
-                        ),
+                        scheduled_fn_count: {
+                            let mut cnt = 0;
+                            self.fn_containers_for_each(f.fn_id, |c| cnt += c.req_fn_state.len());
+                            cnt
+                        },
                         ready_2_schedule_fn_reqs: vec![],
                     },
                 )
@@ -71,15 +65,18 @@ impl SimEnv {
             // 遍历DAG
             'outer: while let Some(f) = walker.next(&req_dag.dag_inner) {
                 let fnid = req_dag.dag_inner[f];
-                // 如果函数已执行完成或已调度，则跳过
-                if req.done_fns.contains_key(&fnid) || req.fn_node.contains_key(&fnid) {
+                // 如果函数已执行完成、已调度或在本次请求中不执行，则跳过
+                if req.done_fns.contains_key(&fnid)
+                    || req.fn_node.contains_key(&fnid)
+                    || !req.fn_active(fnid)
+                {
                     // log::info!("req {} fn {} done, no need to scale for", req.req_id, fnid,);
                     continue;
                 }
 
                 //确定前驱已完成
                 // 检查其所有前驱函数是否都已完成且已调度。若有未完成或未调度的前驱函数，则跳过当前函数
-                let parent_fns = env.func(fnid).parent_fns(env);
+                let parent_fns = req.parent_fns(env, fnid);
                 for p in &parent_fns {
                     if req.get_fn_node(*p).is_none() || !req.done_fns.contains_key(p) {
                         // exist a parent fn not done
@@ -123,8 +120,8 @@ impl SimEnv {
                     continue;
                 }
 
-                //已经调度
-                if req.fn_node.contains_key(&fnid) {
+                //已经调度或在本次请求中不执行
+                if req.fn_node.contains_key(&fnid) || !req.fn_active(fnid) {
                     continue;
                 }
 
@@ -138,22 +135,12 @@ impl SimEnv {
                         metric.ready_2_schedule_fn_reqs.push(req.req_id);
                     })
                     .or_insert(ContainerMetric {
-                        container_count: env
-                            .core
-                            .fn_2_nodes()
-                            .get(&fnid)
-                            .map_or_else(|| 0, |nodes| nodes.len()),
-                        scheduled_fn_count: env.core.fn_2_nodes().get(&fnid).map_or_else(
-                            || 0,
-                            |nodes| {
-                                nodes
-                                    .iter()
-                                    .map(|n| {
-                                        env.node(*n).container(fnid).unwrap().req_fn_state.len()
-                                    })
-                                    .sum()
-                            },
-                        ),
+                        container_count: env.fn_container_cnt(fnid),
+                        scheduled_fn_count: {
+                            let mut cnt = 0;
+                            env.fn_containers_for_each(fnid, |c| cnt += c.req_fn_state.len());
+                            cnt
+                        },
                         ready_2_schedule_fn_reqs: vec![req.req_id],
                     });
             }
@@ -0,0 +1,321 @@
+use std::fs;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    cold_start::ColdStartPhase,
+    config::{FanOutConfig, VarDistr},
+};
+
+/// 应用描述文件，声明应用、函数的资源需求以及函数间的调用关系
+///
+/// ```yaml
+/// apps:
+///   - name: thumbnail
+///     weight: 2.0
+///     slo: 10
+///     fns:
+///       - name: resize
+///         cpu: 50
+///         mem: 300
+///         out_put_size: 10
+///         cpu_distr: { distr: lognormal, sigma: 0.5 }
+///         concurrency: 1
+///       - { name: upload, cpu: 20, mem: 200, out_put_size: 1 }
+///     edges:
+///       - { from: resize, to: upload, data_size: 8 }
+///   - name: order
+///     fns:
+///       - { name: check, cpu: 10, mem: 100, out_put_size: 1, choice: true }
+///       - { name: pay, cpu: 30, mem: 200, out_put_size: 1 }
+///       - { name: reject, cpu: 5, mem: 100, out_put_size: 1 }
+///     uses: [auth]
+///     edges:
+///       - { from: auth, to: check }
+///       - { from: check, to: pay, prob: 0.9 }
+///       - { from: check, to: reject, prob: 0.1 }
+/// shared_fns:
+///   - { name: auth, cpu: 5, mem: 100, out_put_size: 1 }
+/// ```
+#[derive(Serialize, Deserialize, Clone)]
+pub struct AppSpec {
+    pub apps: Vec<AppSpecApp>,
+    /// 共享函数，被多个应用通过 uses 引用，这些应用共用同一个函数的容器
+    #[serde(default)]
+    pub shared_fns: Vec<FnSpec>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct AppSpecApp {
+    pub name: String,
+    /// 应用被选中的权重，默认为 1
+    #[serde(default)]
+    pub weight: Option<f32>,
+    /// 应用每帧的平均请求数，设置后按应用各自的速率生成请求，此时所有应用都需要设置
+    #[serde(default)]
+    pub rate: Option<f32>,
+    /// 端到端延迟 SLO（帧），不设置时按关键路径长度计算
+    #[serde(default)]
+    pub slo: Option<usize>,
+    pub fns: Vec<FnSpec>,
+    /// 应用引用的共享函数名
+    #[serde(default)]
+    pub uses: Vec<String>,
+    #[serde(default)]
+    pub edges: Vec<EdgeSpec>,
+}
+
+/// 函数的资源需求，未设置的冷启动参数随机生成
+#[derive(Serialize, Deserialize, Clone)]
+pub struct FnSpec {
+    pub name: String,
+    /// 运算量
+    pub cpu: f32,
+    /// 运行时占用内存 mb
+    pub mem: f32,
+    /// 输出数据量 mb
+    pub out_put_size: f32,
+    /// 冷启动帧数
+    #[serde(default)]
+    pub cold_start_time: Option<usize>,
+    #[serde(default)]
+    pub cold_start_container_mem_use: Option<f32>,
+    #[serde(default)]
+    pub cold_start_container_cpu_use: Option<f32>,
+    /// 冷启动各阶段，如 [{ phase: sandbox, frames: 1, cpu: 5, mem: 50 }, ...]，
+    /// 设置后忽略上面三个整体参数
+    #[serde(default)]
+    pub cold_start_phases: Option<Vec<ColdStartPhase>>,
+    /// 选择函数，每次请求只按出边的 prob 加权选择一个后继执行
+    #[serde(default)]
+    pub choice: bool,
+    /// 动态 map 函数，每次请求按分布抽取并行实例数，如 { distr: uniform, min: 2, max: 8 }
+    #[serde(default)]
+    pub fan_out: Option<FanOutConfig>,
+    /// 每次执行的运算量倍数分布，如 { distr: lognormal, sigma: 0.5 }，不设置时使用 exec_var 配置
+    #[serde(default)]
+    pub cpu_distr: Option<VarDistr>,
+    /// 每次执行的输出数据量倍数分布，如 { distr: empirical, values: [0.5, 1.0, 1.5] }
+    #[serde(default)]
+    pub out_distr: Option<VarDistr>,
+    /// 运算量和输出数据量与请求输入规模的相关程度 (0~1)
+    #[serde(default)]
+    pub input_corr: Option<f32>,
+    /// 每个容器同时执行的任务数，不设置时使用 container_concurrency 配置
+    #[serde(default)]
+    pub concurrency: Option<usize>,
+    /// 函数的运行时，可以特化同一运行时的预热通用容器，不设置时从 stem_cell 配置中选择
+    #[serde(default)]
+    pub runtime: Option<String>,
+    /// 函数使用的共享基础层序号，不设置时随机选择，只在设置了 image 配置时有效
+    #[serde(default)]
+    pub base_layer: Option<usize>,
+    /// 函数层大小 mb，不设置时随机生成
+    #[serde(default)]
+    pub image_size: Option<f32>,
+}
+
+/// from 调用 to，传输 data_size mb 数据，不设置时为 from 的输出数据量
+#[derive(Serialize, Deserialize, Clone)]
+pub struct EdgeSpec {
+    pub from: String,
+    pub to: String,
+    #[serde(default)]
+    pub data_size: Option<f32>,
+    /// 每次请求中 from 执行后调用 to 的概率，不设置时总是调用；
+    /// from 为选择函数时作为选择该边的权重
+    #[serde(default)]
+    pub prob: Option<f32>,
+}
+
+impl AppSpec {
+    /// 读取 .yaml/.yml 或 .json 格式的应用描述文件
+    pub fn load(path: &str) -> Self {
+        let content = fs::read_to_string(path)
+            .unwrap_or_else(|e| panic!("read app spec {} failed: {}", path, e));
+        let spec: AppSpec = if path.ends_with(".yaml") || path.ends_with(".yml") {
+            serde_yaml::from_str(&content)
+                .unwrap_or_else(|e| panic!("invalid app spec {}: {}", path, e))
+        } else {
+            serde_json::from_str(&content)
+                .unwrap_or_else(|e| panic!("invalid app spec {}: {}", path, e))
+        };
+        assert!(!spec.apps.is_empty(), "app spec {} has no app", path);
+        for app in &spec.apps {
+            assert!(
+                !app.fns.is_empty() || !app.uses.is_empty(),
+                "app {} in app spec {} has no fn",
+                app.name,
+                path
+            );
+            assert!(
+                app.weight.is_none_or(|w| w >= 0.0),
+                "app {} in app spec {} has negative weight",
+                app.name,
+                path
+            );
+        }
+        assert!(
+            spec.apps.iter().map(|a| a.weight.unwrap_or(1.0)).sum::<f32>() > 0.0,
+            "app spec {} has no app with positive weight",
+            path
+        );
+        let rate_cnt = spec.apps.iter().filter(|a| a.rate.is_some()).count();
+        assert!(
+            rate_cnt == 0 || rate_cnt == spec.apps.len(),
+            "app spec {} should set rate for all or none of the apps, {} of {} set",
+            path,
+            rate_cnt,
+            spec.apps.len()
+        );
+        spec
+    }
+
+    // 是否按应用各自的请求速率生成请求
+    pub fn rate_set(&self) -> bool {
+        self.apps.iter().any(|a| a.rate.is_some())
+    }
+
+    // 应用中的所有函数：先是应用自己的函数，然后是引用的共享函数
+    pub fn app_fns<'a>(&'a self, app: &'a AppSpecApp) -> Vec<&'a FnSpec> {
+        let mut fns: Vec<&FnSpec> = app.fns.iter().collect();
+        for name in &app.uses {
+            let shared = self
+                .shared_fns
+                .iter()
+                .find(|f| &f.name == name)
+                .unwrap_or_else(|| panic!("app {} uses unknown shared fn {}", app.name, name));
+            fns.push(shared);
+        }
+        fns
+    }
+}
+
+impl AppSpecApp {
+    // 函数名在应用的函数列表中的下标
+    pub fn fn_idx(&self, fns: &[&FnSpec], name: &str) -> usize {
+        fns.iter()
+            .position(|f| f.name == name)
+            .unwrap_or_else(|| panic!("app {} has no fn {}", self.name, name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 写入临时文件后读取，读取失败的 panic 在删除文件后继续抛出
+    fn load_str(name: &str, content: &str) -> AppSpec {
+        let path = std::env::temp_dir().join(format!("{}_{}", std::process::id(), name));
+        let path = path.to_str().unwrap();
+        fs::write(path, content).unwrap();
+        let res = std::panic::catch_unwind(|| AppSpec::load(path));
+        fs::remove_file(path).unwrap();
+        res.unwrap_or_else(|e| std::panic::resume_unwind(e))
+    }
+
+    #[test]
+    fn load_yaml() {
+        let spec = load_str(
+            "spec.yaml",
+            "apps:
+  - name: thumbnail
+    weight: 2.0
+    slo: 10
+    fns:
+      - { name: resize, cpu: 50, mem: 300, out_put_size: 10, concurrency: 1 }
+      - { name: upload, cpu: 20, mem: 200, out_put_size: 1 }
+    edges:
+      - { from: resize, to: upload, data_size: 8 }
+  - name: order
+    fns:
+      - { name: check, cpu: 10, mem: 100, out_put_size: 1 }
+    uses: [auth]
+    edges:
+      - { from: auth, to: check }
+shared_fns:
+  - { name: auth, cpu: 5, mem: 100, out_put_size: 1 }
+",
+        );
+        assert_eq!(spec.apps.len(), 2);
+        assert!(!spec.rate_set());
+        assert_eq!(spec.apps[0].weight, Some(2.0));
+        assert_eq!(spec.apps[0].slo, Some(10));
+        assert_eq!(spec.apps[0].fns[0].concurrency, Some(1));
+        assert_eq!(spec.apps[0].edges[0].data_size, Some(8.0));
+
+        let order = &spec.apps[1];
+        let fns = spec.app_fns(order);
+        assert_eq!(
+            fns.iter().map(|f| &*f.name).collect::<Vec<_>>(),
+            vec!["check", "auth"]
+        );
+        assert_eq!(order.fn_idx(&fns, "auth"), 1);
+    }
+
+    #[test]
+    fn load_json() {
+        let spec = load_str(
+            "spec.json",
+            r#"{"apps": [
+                {"name": "a", "rate": 1.5, "fns": [{"name": "f", "cpu": 1, "mem": 2, "out_put_size": 3}]},
+                {"name": "b", "rate": 0.5, "fns": [{"name": "g", "cpu": 1, "mem": 2, "out_put_size": 3}]}
+            ]}"#,
+        );
+        assert_eq!(spec.apps.len(), 2);
+        assert!(spec.rate_set());
+        assert_eq!(spec.apps[1].rate, Some(0.5));
+    }
+
+    #[test]
+    #[should_panic(expected = "has no app")]
+    fn no_app() {
+        load_str("no_app.yaml", "apps: []\n");
+    }
+
+    #[test]
+    #[should_panic(expected = "has no fn")]
+    fn app_without_fn() {
+        load_str("no_fn.yaml", "apps:\n  - { name: a, fns: [] }\n");
+    }
+
+    #[test]
+    #[should_panic(expected = "has negative weight")]
+    fn negative_weight() {
+        load_str(
+            "neg_weight.yaml",
+            "apps:\n  - { name: a, weight: -1, fns: [{ name: f, cpu: 1, mem: 1, out_put_size: 1 }] }\n",
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "has no app with positive weight")]
+    fn zero_total_weight() {
+        load_str(
+            "zero_weight.yaml",
+            "apps:\n  - { name: a, weight: 0, fns: [{ name: f, cpu: 1, mem: 1, out_put_size: 1 }] }\n",
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "should set rate for all or none of the apps, 1 of 2 set")]
+    fn partial_rate() {
+        load_str(
+            "partial_rate.yaml",
+            "apps:
+  - { name: a, rate: 1, fns: [{ name: f, cpu: 1, mem: 1, out_put_size: 1 }] }
+  - { name: b, fns: [{ name: g, cpu: 1, mem: 1, out_put_size: 1 }] }
+",
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "app order uses unknown shared fn auth")]
+    fn unknown_shared_fn() {
+        let spec = load_str(
+            "unknown_shared.yaml",
+            "apps:\n  - { name: order, uses: [auth], fns: [] }\n",
+        );
+        spec.app_fns(&spec.apps[0]);
+    }
+}
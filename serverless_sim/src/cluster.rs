@@ -0,0 +1,208 @@
+use std::{collections::BTreeMap, fs};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{NODE_CNT, NODE_LEFT_MEM_THRESHOLD};
+
+/// 集群描述，声明各类节点的资源、速度和标签，节点按声明顺序编号
+///
+/// ```yaml
+/// nodes:
+///   - { count: 6, cpu: 1000, mem: 8000, labels: { zone: a, arch: x86, tier: standard } }
+///   - { count: 2, cpu: 2000, mem: 16000, speed: 1.5, labels: { zone: b, arch: x86, tier: large } }
+///   - { count: 2, cpu: 800, mem: 6000, speed: 0.8, labels: { zone: b, arch: arm, tier: edge } }
+/// ```
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ClusterSpec {
+    pub nodes: Vec<NodeSpec>,
+}
+
+/// 一类节点
+#[derive(Serialize, Deserialize, Clone)]
+pub struct NodeSpec {
+    /// 该类节点的个数
+    #[serde(default = "default_node_count")]
+    pub count: usize,
+    /// 每帧可分配的 cpu
+    #[serde(default = "default_node_cpu")]
+    pub cpu: f32,
+    /// 内存 mb
+    #[serde(default = "default_node_mem")]
+    pub mem: f32,
+    /// 执行速度倍数，分得的每份 cpu 完成 speed 倍的运算量
+    #[serde(default = "default_node_speed")]
+    pub speed: f32,
+    /// 节点标签，如 zone、arch、tier
+    #[serde(default)]
+    pub labels: BTreeMap<String, String>,
+}
+
+fn default_node_count() -> usize {
+    1
+}
+
+fn default_node_cpu() -> f32 {
+    1000.0
+}
+
+fn default_node_mem() -> f32 {
+    8000.0
+}
+
+fn default_node_speed() -> f32 {
+    1.0
+}
+
+impl NodeSpec {
+    pub fn str(&self) -> String {
+        format!("{}x{}c{}m{}s", self.count, self.cpu, self.mem, self.speed)
+    }
+}
+
+impl ClusterSpec {
+    /// 读取 .yaml/.yml 或 .json 格式的集群描述文件
+    pub fn load(path: &str) -> Self {
+        let content = fs::read_to_string(path)
+            .unwrap_or_else(|e| panic!("read cluster spec {} failed: {}", path, e));
+        let spec: ClusterSpec = if path.ends_with(".yaml") || path.ends_with(".yml") {
+            serde_yaml::from_str(&content)
+                .unwrap_or_else(|e| panic!("invalid cluster spec {}: {}", path, e))
+        } else {
+            serde_json::from_str(&content)
+                .unwrap_or_else(|e| panic!("invalid cluster spec {}: {}", path, e))
+        };
+        assert!(!spec.nodes.is_empty(), "cluster spec {} has no node", path);
+        spec
+    }
+
+    /// node_cnt 个相同的默认节点
+    pub fn uniform(node_cnt: usize) -> Self {
+        Self {
+            nodes: vec![NodeSpec {
+                count: node_cnt,
+                cpu: default_node_cpu(),
+                mem: default_node_mem(),
+                speed: default_node_speed(),
+                labels: BTreeMap::new(),
+            }],
+        }
+    }
+
+    // 按节点编号展开的每个节点的描述
+    pub fn node_specs(&self) -> Vec<&NodeSpec> {
+        let specs: Vec<&NodeSpec> = self
+            .nodes
+            .iter()
+            .flat_map(|n| std::iter::repeat_n(n, n.count))
+            .collect();
+        assert!(!specs.is_empty(), "cluster should have at least one node");
+        for n in &specs {
+            assert!(
+                n.cpu > 0.0 && n.speed > 0.0,
+                "node cpu {} and speed {} should be positive",
+                n.cpu,
+                n.speed
+            );
+            assert!(
+                n.mem > NODE_LEFT_MEM_THRESHOLD,
+                "node mem {} should be larger than the reserved {}",
+                n.mem,
+                NODE_LEFT_MEM_THRESHOLD
+            );
+        }
+        specs
+    }
+
+    pub fn str(&self) -> String {
+        self.nodes
+            .iter()
+            .map(|n| n.str())
+            .collect::<Vec<_>>()
+            .join(",")
+    }
+}
+
+pub fn default_node_cnt() -> usize {
+    NODE_CNT
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 写入临时文件后读取，读取失败的 panic 在删除文件后继续抛出
+    fn load_str(name: &str, content: &str) -> ClusterSpec {
+        let path = std::env::temp_dir().join(format!("{}_{}", std::process::id(), name));
+        let path = path.to_str().unwrap();
+        fs::write(path, content).unwrap();
+        let res = std::panic::catch_unwind(|| ClusterSpec::load(path));
+        fs::remove_file(path).unwrap();
+        res.unwrap_or_else(|e| std::panic::resume_unwind(e))
+    }
+
+    #[test]
+    fn load_yaml() {
+        let spec = load_str(
+            "cluster.yaml",
+            "nodes:
+  - { count: 2, cpu: 2000, mem: 16000, speed: 1.5, labels: { zone: b, tier: large } }
+  - { mem: 6000 }
+",
+        );
+        let nodes = spec.node_specs();
+        assert_eq!(nodes.len(), 3);
+        assert_eq!(nodes[1].cpu, 2000.0);
+        assert_eq!(nodes[1].speed, 1.5);
+        assert_eq!(nodes[1].labels["zone"], "b");
+        // 未设置的字段取默认值
+        assert_eq!(nodes[2].cpu, 1000.0);
+        assert_eq!(nodes[2].mem, 6000.0);
+        assert_eq!(nodes[2].speed, 1.0);
+        assert!(nodes[2].labels.is_empty());
+        assert_eq!(spec.str(), "2x2000c16000m1.5s,1x1000c6000m1s");
+    }
+
+    #[test]
+    fn load_json() {
+        let spec = load_str("cluster.json", r#"{"nodes": [{"count": 4, "cpu": 500}]}"#);
+        let nodes = spec.node_specs();
+        assert_eq!(nodes.len(), 4);
+        assert!(nodes.iter().all(|n| n.cpu == 500.0));
+    }
+
+    #[test]
+    fn uniform() {
+        let spec = ClusterSpec::uniform(NODE_CNT);
+        assert_eq!(spec.node_specs().len(), NODE_CNT);
+    }
+
+    #[test]
+    #[should_panic(expected = "has no node")]
+    fn no_node() {
+        load_str("no_node.yaml", "nodes: []\n");
+    }
+
+    #[test]
+    #[should_panic(expected = "cluster should have at least one node")]
+    fn zero_count() {
+        load_str("zero_count.yaml", "nodes:\n  - { count: 0 }\n").node_specs();
+    }
+
+    #[test]
+    #[should_panic(expected = "should be positive")]
+    fn zero_speed() {
+        load_str("zero_speed.yaml", "nodes:\n  - { speed: 0 }\n").node_specs();
+    }
+
+    #[test]
+    #[should_panic(expected = "should be larger than the reserved")]
+    fn mem_below_reserved() {
+        ClusterSpec {
+            nodes: vec![NodeSpec {
+                mem: NODE_LEFT_MEM_THRESHOLD,
+                ..ClusterSpec::uniform(1).nodes[0].clone()
+            }],
+        }
+        .node_specs();
+    }
+}
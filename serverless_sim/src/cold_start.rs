@@ -0,0 +1,143 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{config::ColdStartPhaseConfig, fn_type::FnArchetype, sim_env::SimEnv};
+
+/// 冷启动的阶段，按顺序执行
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum ColdStartPhaseKind {
+    /// 创建沙箱（容器/microVM）
+    Sandbox,
+    /// 拉取镜像
+    ImagePull,
+    /// 语言运行时初始化
+    RuntimeInit,
+    /// 用户代码初始化（加载依赖、模型等）
+    UserInit,
+    /// 从快照恢复
+    Restore,
+}
+
+impl ColdStartPhaseKind {
+    pub fn str(&self) -> &'static str {
+        match self {
+            ColdStartPhaseKind::Sandbox => "sb",
+            ColdStartPhaseKind::ImagePull => "ip",
+            ColdStartPhaseKind::RuntimeInit => "rt",
+            ColdStartPhaseKind::UserInit => "ui",
+            ColdStartPhaseKind::Restore => "rs",
+        }
+    }
+}
+
+/// 新建容器的启动方式
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum StartMode {
+    /// 完整冷启动
+    Cold,
+    /// 由预热通用容器特化
+    StemCell,
+    /// 从快照恢复
+    Snapshot,
+}
+
+/// 函数冷启动的一个阶段，容器在该阶段的每一帧占用 cpu 运算量和 mem 内存
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+pub struct ColdStartPhase {
+    pub phase: ColdStartPhaseKind,
+    pub frames: usize,
+    pub cpu: f32,
+    pub mem: f32,
+}
+
+fn phase(
+    kind: ColdStartPhaseKind,
+    frames: (usize, usize),
+    cpu: (f32, f32),
+    mem: (f32, f32),
+) -> ColdStartPhaseConfig {
+    ColdStartPhaseConfig::new(kind, frames, cpu, mem)
+}
+
+// 各冷启动配置下每个阶段的 (帧数, cpu, mem) 范围，总帧数与原来的 5~10 帧相当
+fn high_profile() -> Vec<ColdStartPhaseConfig> {
+    use ColdStartPhaseKind::*;
+    vec![
+        phase(Sandbox, (1, 2), (5.0, 15.0), (30.0, 60.0)),
+        phase(ImagePull, (2, 4), (1.0, 5.0), (30.0, 60.0)),
+        phase(RuntimeInit, (1, 2), (10.0, 40.0), (100.0, 300.0)),
+        phase(UserInit, (1, 2), (10.0, 50.0), (150.0, 500.0)),
+    ]
+}
+
+// 镜像已缓存、运行时轻量的冷启动，总帧数 2~4
+fn low_profile() -> Vec<ColdStartPhaseConfig> {
+    use ColdStartPhaseKind::*;
+    vec![
+        phase(Sandbox, (1, 1), (1.0, 5.0), (20.0, 40.0)),
+        phase(ImagePull, (0, 1), (0.5, 2.0), (20.0, 40.0)),
+        phase(RuntimeInit, (1, 1), (5.0, 20.0), (50.0, 150.0)),
+        phase(UserInit, (0, 1), (5.0, 20.0), (100.0, 250.0)),
+    ]
+}
+
+/// 冷启动的总帧数、最大内存占用和最大cpu占用
+pub fn phases_summary(phases: &[ColdStartPhase]) -> (usize, f32, f32) {
+    (
+        phases.iter().map(|p| p.frames).sum(),
+        phases.iter().map(|p| p.mem).fold(0.0, f32::max),
+        phases.iter().map(|p| p.cpu).fold(0.0, f32::max),
+    )
+}
+
+impl SimEnv {
+    /// 按 cold_start 配置为新函数生成冷启动阶段：
+    /// high、low 使用对应的配置，mix 每个函数等概率选择 high 或 low，
+    /// 设置了 cold_start_phases 时使用自定义的阶段范围，再按函数原型调整
+    pub fn fn_gen_cold_start_phases(&self, archetype: Option<FnArchetype>) -> Vec<ColdStartPhase> {
+        let mut profile = if let Some(phases) = &self.help.config().cold_start_phases {
+            phases.clone()
+        } else {
+            match &*self.help.config().cold_start {
+                "high" => high_profile(),
+                "low" => low_profile(),
+                "mix" => {
+                    if self.env_rand_f(0.0, 1.0) < 0.5 {
+                        high_profile()
+                    } else {
+                        low_profile()
+                    }
+                }
+                cs => panic!("not support cold start {}, should be high, low or mix", cs),
+            }
+        };
+        if let Some(archetype) = archetype {
+            archetype.scale_cold_start(&mut profile);
+        }
+        let phases: Vec<ColdStartPhase> = profile
+            .iter()
+            .map(|p| ColdStartPhase {
+                phase: p.phase,
+                frames: self.env_rand_i(p.frames.0, p.frames.1 + 1),
+                cpu: self.env_rand_f_incl(p.cpu.0, p.cpu.1),
+                mem: self.env_rand_f_incl(p.mem.0, p.mem.1),
+            })
+            // 为 0 帧的阶段被跳过
+            .filter(|p| p.frames > 0)
+            .collect();
+        assert!(
+            !phases.is_empty(),
+            "cold start should take at least one frame"
+        );
+        phases
+    }
+
+    // [min, max]，min == max 时直接返回 min
+    pub fn env_rand_f_incl(&self, min: f32, max: f32) -> f32 {
+        if min >= max {
+            min
+        } else {
+            self.env_rand_f(min, max)
+        }
+    }
+}
@@ -1,14 +1,22 @@
 use core::panic;
 use std::{
-    collections::{hash_map, HashMap},
+    collections::{hash_map, BTreeMap, HashMap},
     fs::File,
 };
 
 use clap::builder::Str;
+use rand::Rng;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
-use crate::mechanism_conf::MechConfig;
+use crate::{
+    app_spec::AppSpec,
+    cluster::{self, ClusterSpec},
+    cold_start::ColdStartPhaseKind,
+    fn_type::FnArchetype,
+    mechanism_conf::MechConfig,
+    util, NODE_CNT,
+};
 
 // 存储应用配置信息
 #[derive(Serialize, Deserialize, Clone)]
@@ -16,22 +24,60 @@ pub struct APPConfig {
     // 应用的数量
     pub app_cnt: usize,
     // 表示请求频率
+    #[serde(default)]
     pub request_freq: String,
     /// dag type: single, chain, dag
     pub dag_type: String,
     /// cold start: high, low, mix
     /// 冷启动情况
+    #[serde(default)]
     pub cold_start: String,
     /// cpu, memory,datasize
     // pub fn_cpu: String,
     // pub fn_mem: String,
     // pub fn_data: String,
     // 函数的CPU、内存和数据大小需求
+    #[serde(default)]
     pub fn_cpu: f32,
+    #[serde(default)]
     pub fn_mem: f32,
+    #[serde(default)]
     pub fn_data: f32,
     /// is time sensitive app=1
+    #[serde(default)]
     pub app_is_sens: bool,
+    /// 这类应用被选中的总权重，不设置时为每个应用权重 1
+    #[serde(default)]
+    pub weight: Option<f32>,
+    /// 这类应用内部按 Zipf 分布分配权重的指数，不设置时均分
+    #[serde(default)]
+    pub zipf: Option<f32>,
+    /// 这类应用中每个应用每帧的平均请求数（泊松到达），设置后按应用各自的速率生成请求，
+    /// 此时所有应用类型都需要设置
+    #[serde(default)]
+    pub rate: Option<f32>,
+    /// 端到端延迟 SLO（帧），不设置时按关键路径长度计算
+    #[serde(default)]
+    pub slo: Option<usize>,
+}
+
+impl APPConfig {
+    pub fn str(&self) -> String {
+        let mut s = format!("{}{}", self.dag_type, self.app_cnt);
+        if let Some(weight) = self.weight {
+            s += &format!("w{}", weight);
+        }
+        if let Some(zipf) = self.zipf {
+            s += &format!("z{}", zipf);
+        }
+        if let Some(rate) = self.rate {
+            s += &format!("r{}", rate);
+        }
+        if let Some(slo) = self.slo {
+            s += &format!("slo{}", slo);
+        }
+        s
+    }
 }
 
 #[derive(Serialize, Deserialize, Clone)]
@@ -40,17 +86,749 @@ pub struct Config {
     pub rand_seed: String,
     /// low middle high
     pub request_freq: String,
-    /// dag type: single, chain, dag, mix
+    /// dag type: single, chain, dag, mix, layered, series_parallel, dynamic_map
     pub dag_type: String,
     /// cold start: high, low, mix
     pub cold_start: String,
-    /// cpu, data, mix
+    /// 自定义冷启动各阶段的参数范围，设置后替代 cold_start 对应的配置
+    #[serde(default)]
+    pub cold_start_phases: Option<Vec<ColdStartPhaseConfig>>,
+    /// cpu, data, mix, 或者函数原型名（cpu_bound, io_bound, memory_heavy, data_producing, ml_inference）
     pub fn_type: String,
+    /// fn_type 为 mix 时各函数原型的权重，如 {"cpu_bound": 2, "ml_inference": 1}，不设置时等权重
+    #[serde(default)]
+    pub fn_type_weights: Option<BTreeMap<FnArchetype, f32>>,
+    /// 应用类型，不设置时按 dag_type 生成应用
+    #[serde(default)]
+    pub app_types: Vec<APPConfig>,
+    /// 应用 DAG 的生成参数
+    #[serde(default)]
+    pub dag_gen: Option<DagGenConfig>,
+    /// 应用描述文件（.yaml/.yml/.json），设置后按文件生成应用和函数
+    #[serde(default)]
+    pub app_spec: Option<String>,
     /// each stage control algorithm settings
-    // pub app_types: Vec<APPConfig>,
     pub mech: MechConfig,
     /// whether to log the resultz
     pub no_log: bool,
+    /// 请求生成方式，不设置时按 request_freq 生成
+    #[serde(default)]
+    pub workload: Option<WorkloadConfig>,
+    /// 将生成的请求记录到该 jsonl 文件，可通过 replay 类型的 workload 回放
+    #[serde(default)]
+    pub workload_record: Option<String>,
+    /// 准入控制，不设置时接收所有请求
+    #[serde(default)]
+    pub admission: Option<AdmissionConfig>,
+    /// 请求超时（帧），超时未完成的请求被中止，不设置时不超时
+    #[serde(default)]
+    pub req_timeout: Option<usize>,
+    /// 导出应用 DAG 和请求执行过程，不设置时不导出
+    #[serde(default)]
+    pub export: Option<ExportConfig>,
+    /// 函数每次执行的运算量和输出数据量的随机波动，不设置时每次执行都等于函数的均值
+    #[serde(default)]
+    pub exec_var: Option<ExecVarConfig>,
+    /// 每个节点上同一函数最多的容器副本数
+    #[serde(default = "default_max_replicas_per_node")]
+    pub max_replicas_per_node: usize,
+    /// 每个容器同时执行的任务数，超出的任务在容器前排队（FIFO），不设置时不限制，
+    /// 应用描述文件中可以为函数单独设置
+    #[serde(default)]
+    pub container_concurrency: Option<usize>,
+    /// 节点上各运行时的预热通用容器池，不设置时不预热
+    #[serde(default)]
+    pub stem_cell: Option<StemCellConfig>,
+    /// 容器快照，函数第一次冷启动后保存快照，之后的启动从快照恢复，不设置时不使用快照
+    #[serde(default)]
+    pub snapshot: Option<SnapshotConfig>,
+    /// 函数镜像和节点的本地镜像缓存，不设置时不模拟镜像拉取
+    #[serde(default)]
+    pub image: Option<ImageConfig>,
+    /// 节点数，设置了 cluster 或 cluster_spec 时由集群描述决定
+    #[serde(default = "cluster::default_node_cnt")]
+    pub node_cnt: usize,
+    /// 集群描述，不设置时为 node_cnt 个相同的节点
+    #[serde(default)]
+    pub cluster: Option<ClusterSpec>,
+    /// 集群描述文件（.yaml/.yml/.json），不能与 cluster 同时设置
+    #[serde(default)]
+    pub cluster_spec: Option<String>,
+    /// 节点故障注入，不设置时节点不会故障
+    #[serde(default)]
+    pub fault: Option<FaultConfig>,
+}
+
+fn default_max_replicas_per_node() -> usize {
+    1
+}
+
+/// 应用 DAG 的生成参数
+#[derive(Serialize, Deserialize, Clone)]
+pub struct DagGenConfig {
+    /// 按 dag_type 生成的应用数量，不设置时 single、mix 为 10 个，其它为 6 个
+    #[serde(default)]
+    pub app_cnt: Option<usize>,
+    /// chain 的函数个数范围 [min, max]
+    #[serde(default = "default_chain_len")]
+    pub chain_len: (usize, usize),
+    /// mix 中 single、chain、dag 三种应用的权重
+    #[serde(default = "default_mix_weights")]
+    pub mix_weights: (f32, f32, f32),
+    /// layered 的层数范围 [min, max]，第一层只有起始函数
+    #[serde(default = "default_layer_depth")]
+    pub depth: (usize, usize),
+    /// layered 每层的函数个数范围 [min, max]
+    #[serde(default = "default_layer_width")]
+    pub width: (usize, usize),
+    /// layered 中函数与更前面各层的每个函数额外连边的概率
+    #[serde(default = "default_edge_density")]
+    pub density: f32,
+    /// layered 中每个函数的前驱函数个数上限，不设置时不限制
+    #[serde(default)]
+    pub max_fan_in: Option<usize>,
+    /// series_parallel 的函数个数范围 [min, max]
+    #[serde(default = "default_sp_fn_cnt")]
+    pub sp_fn_cnt: (usize, usize),
+    /// series_parallel 每次扩展时并联（否则串联）的概率
+    #[serde(default = "default_sp_parallel_prob")]
+    pub sp_parallel_prob: f32,
+    /// 边上数据量的范围 [min, max)，不设置时为前驱函数的输出数据量
+    #[serde(default)]
+    pub edge_size: Option<(f32, f32)>,
+    /// 有多个后继的函数成为选择函数（每次请求只执行一个后继）的概率
+    #[serde(default)]
+    pub choice_prob: f32,
+    /// dynamic_map 中 map 函数每次请求的并行实例数分布
+    #[serde(default = "default_fan_out")]
+    pub fan_out: FanOutConfig,
+    /// 共享函数池的大小，池中的函数可以被多个应用引用，为 0 时每个应用的函数都是独立的
+    #[serde(default)]
+    pub shared_fn_cnt: usize,
+    /// 生成应用的函数时复用共享函数的概率
+    #[serde(default = "default_shared_fn_prob")]
+    pub shared_fn_prob: f32,
+}
+
+impl Default for DagGenConfig {
+    fn default() -> Self {
+        Self {
+            app_cnt: None,
+            chain_len: default_chain_len(),
+            mix_weights: default_mix_weights(),
+            depth: default_layer_depth(),
+            width: default_layer_width(),
+            density: default_edge_density(),
+            max_fan_in: None,
+            sp_fn_cnt: default_sp_fn_cnt(),
+            sp_parallel_prob: default_sp_parallel_prob(),
+            edge_size: None,
+            choice_prob: 0.0,
+            fan_out: default_fan_out(),
+            shared_fn_cnt: 0,
+            shared_fn_prob: default_shared_fn_prob(),
+        }
+    }
+}
+
+impl DagGenConfig {
+    pub fn str(&self) -> String {
+        format!(
+            "{}c{}_{}m{}_{}_{}",
+            self.app_cnt.map_or_else(String::new, |c| format!("n{}", c)),
+            self.chain_len.0,
+            self.chain_len.1,
+            self.mix_weights.0,
+            self.mix_weights.1,
+            self.mix_weights.2
+        ) + &format!(
+            "l{}_{}_{}_{}_{}{}sp{}_{}_{}{}",
+            self.depth.0,
+            self.depth.1,
+            self.width.0,
+            self.width.1,
+            self.density,
+            self.max_fan_in.map_or_else(String::new, |f| format!("_{}", f)),
+            self.sp_fn_cnt.0,
+            self.sp_fn_cnt.1,
+            self.sp_parallel_prob,
+            self.edge_size
+                .map_or_else(String::new, |(min, max)| format!("e{}_{}", min, max))
+        ) + &if self.choice_prob > 0.0 {
+            format!("ch{}", self.choice_prob)
+        } else {
+            String::new()
+        } + &if self.fan_out != default_fan_out() {
+            format!("fo{}", self.fan_out.str())
+        } else {
+            String::new()
+        } + &if self.shared_fn_cnt > 0 {
+            format!("sh{}_{}", self.shared_fn_cnt, self.shared_fn_prob)
+        } else {
+            String::new()
+        }
+    }
+}
+
+fn default_chain_len() -> (usize, usize) {
+    (3, 5)
+}
+fn default_mix_weights() -> (f32, f32, f32) {
+    (1.0, 1.0, 1.0)
+}
+fn default_layer_depth() -> (usize, usize) {
+    (3, 5)
+}
+fn default_layer_width() -> (usize, usize) {
+    (1, 4)
+}
+fn default_edge_density() -> f32 {
+    0.3
+}
+fn default_sp_fn_cnt() -> (usize, usize) {
+    (4, 10)
+}
+fn default_sp_parallel_prob() -> f32 {
+    0.5
+}
+fn default_shared_fn_prob() -> f32 {
+    0.3
+}
+fn default_fan_out() -> FanOutConfig {
+    FanOutConfig::Uniform { min: 2, max: 8 }
+}
+
+/// 动态 map 函数每次请求的并行实例数分布，实例数至少为 1
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(tag = "distr", rename_all = "snake_case")]
+pub enum FanOutConfig {
+    /// 固定实例数
+    Fixed { width: usize },
+    /// [min, max] 均匀分布
+    Uniform { min: usize, max: usize },
+    /// 均值为 mean 的泊松分布，不超过 max
+    Poisson { mean: f32, max: usize },
+}
+
+impl FanOutConfig {
+    pub fn str(&self) -> String {
+        match self {
+            FanOutConfig::Fixed { width } => format!("{}", width),
+            FanOutConfig::Uniform { min, max } => format!("u{}_{}", min, max),
+            FanOutConfig::Poisson { mean, max } => format!("p{}_{}", mean, max),
+        }
+    }
+
+    /// 为一个请求抽取实例数
+    pub fn draw(&self, rng: &mut impl Rng) -> usize {
+        let width = match *self {
+            FanOutConfig::Fixed { width } => width,
+            FanOutConfig::Uniform { min, max } => {
+                assert!(min <= max, "invalid fan_out range ({}, {})", min, max);
+                rng.gen_range(min..max + 1)
+            }
+            FanOutConfig::Poisson { mean, max } => util::rand_poisson(rng, mean).min(max),
+        };
+        width.max(1)
+    }
+}
+
+/// 随机倍数的分布，均值为 1，函数每次执行的运算量和输出数据量为函数的均值乘以该倍数
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(tag = "distr", rename_all = "snake_case")]
+pub enum VarDistr {
+    /// 对数正态分布，sigma 为倍数取对数后的标准差
+    Lognormal { sigma: f32 },
+    /// 经验分布，等概率取 values 中的一个倍数，如从实测执行时间除以均值得到
+    Empirical { values: Vec<f32> },
+}
+
+impl VarDistr {
+    pub fn str(&self) -> String {
+        match self {
+            VarDistr::Lognormal { sigma } => format!("ln{}", sigma),
+            VarDistr::Empirical { values } => format!("emp{}", values.len()),
+        }
+    }
+
+    pub fn sample(&self, rng: &mut impl Rng) -> f32 {
+        match self {
+            VarDistr::Lognormal { sigma } => {
+                // mu = -sigma^2/2 使均值为 1
+                let sigma = *sigma as f64;
+                (sigma * util::rand_std_normal(rng) - sigma * sigma / 2.0).exp() as f32
+            }
+            VarDistr::Empirical { values } => {
+                assert!(!values.is_empty(), "empirical distribution has no value");
+                values[rng.gen_range(0..values.len())]
+            }
+        }
+    }
+}
+
+/// 函数执行的随机波动，应用到随机生成的函数上，应用描述文件中的函数可单独设置
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ExecVarConfig {
+    /// 运算量的倍数分布
+    #[serde(default)]
+    pub cpu: Option<VarDistr>,
+    /// 输出数据量的倍数分布
+    #[serde(default)]
+    pub output: Option<VarDistr>,
+    /// 请求输入规模的倍数分布，不设置时所有请求的输入规模都为 1
+    #[serde(default)]
+    pub input: Option<VarDistr>,
+    /// 运算量和输出数据量与请求输入规模的相关程度 (0~1)，
+    /// 倍数 = input_corr * 输入规模 + (1 - input_corr) * 函数自身的随机倍数
+    #[serde(default)]
+    pub input_corr: f32,
+}
+
+impl ExecVarConfig {
+    pub fn str(&self) -> String {
+        let distr = |d: &Option<VarDistr>| d.as_ref().map_or_else(|| "-".to_owned(), |d| d.str());
+        format!(
+            "c{}o{}i{}_{}",
+            distr(&self.cpu),
+            distr(&self.output),
+            distr(&self.input),
+            self.input_corr
+        )
+    }
+}
+
+/// 冷启动一个阶段的参数范围（均含两端），函数生成时在范围内抽取
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ColdStartPhaseConfig {
+    pub phase: ColdStartPhaseKind,
+    /// 帧数，为 0 时跳过该阶段
+    pub frames: (usize, usize),
+    /// 每帧占用的运算量
+    pub cpu: (f32, f32),
+    /// 占用的内存 mb
+    pub mem: (f32, f32),
+}
+
+impl ColdStartPhaseConfig {
+    pub fn new(
+        phase: ColdStartPhaseKind,
+        frames: (usize, usize),
+        cpu: (f32, f32),
+        mem: (f32, f32),
+    ) -> Self {
+        Self {
+            phase,
+            frames,
+            cpu,
+            mem,
+        }
+    }
+
+    pub fn str(&self) -> String {
+        format!(
+            "{}{}_{}c{}_{}m{}_{}",
+            self.phase.str(),
+            self.frames.0,
+            self.frames.1,
+            self.cpu.0,
+            self.cpu.1,
+            self.mem.0,
+            self.mem.1
+        )
+    }
+}
+
+/// 准入控制，新请求到达时超过任一阈值则拒绝
+#[derive(Serialize, Deserialize, Clone)]
+pub struct AdmissionConfig {
+    /// 系统中未完成请求数上限
+    #[serde(default)]
+    pub max_queue: Option<usize>,
+    /// 上一帧集群平均 cpu 利用率上限 (0~1)
+    #[serde(default)]
+    pub max_util: Option<f32>,
+}
+
+impl AdmissionConfig {
+    pub fn str(&self) -> String {
+        let mut s = String::new();
+        if let Some(max_queue) = self.max_queue {
+            s += &format!("q{}", max_queue);
+        }
+        if let Some(max_util) = self.max_util {
+            s += &format!("u{}", max_util);
+        }
+        s
+    }
+}
+
+/// 导出应用 DAG 和请求执行过程到 Graphviz DOT 和 JSON 文件
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ExportConfig {
+    /// 导出目录
+    pub dir: String,
+    /// 额外导出 DOT 图的已完成请求数
+    #[serde(default)]
+    pub req_dot_cnt: usize,
+}
+
+/// 节点上的预热通用容器池（stem cell），预热容器只完成了沙箱创建和运行时初始化，
+/// 同一运行时的任意函数可以将其特化，只需经过剩余的冷启动阶段
+#[derive(Serialize, Deserialize, Clone)]
+pub struct StemCellConfig {
+    /// 每个节点上各运行时的预热容器数，如 {"python": 2, "nodejs": 1}，函数的运行时从中等概率选择
+    pub pool: BTreeMap<String, usize>,
+    /// 后台补充一个预热容器需要的帧数
+    #[serde(default = "default_stem_cell_warm_frames")]
+    pub warm_frames: usize,
+    /// 每个预热容器占用的内存 mb
+    #[serde(default = "default_stem_cell_mem")]
+    pub mem: f32,
+}
+
+fn default_stem_cell_warm_frames() -> usize {
+    3
+}
+
+fn default_stem_cell_mem() -> f32 {
+    100.0
+}
+
+impl StemCellConfig {
+    pub fn str(&self) -> String {
+        format!(
+            "{}.w{}.m{}",
+            self.pool
+                .iter()
+                .map(|(rt, cnt)| format!("{}{}", rt, cnt))
+                .collect::<Vec<_>>()
+                .join(","),
+            self.warm_frames,
+            self.mem
+        )
+    }
+}
+
+/// 容器快照（类似 CRIU、Firecracker snapshot），容器完成启动后保存快照，
+/// 之后的启动直接从快照恢复，耗时取决于快照大小和快照所在位置
+#[derive(Serialize, Deserialize, Clone)]
+pub struct SnapshotConfig {
+    /// 快照保存的位置：
+    /// local 保存在启动容器的节点上，只有该节点可以使用；
+    /// shared 保存在所有节点共享的存储中；
+    /// both 同时保存，优先从本地恢复，从共享存储恢复的节点也会保存一份本地快照
+    #[serde(default = "default_snapshot_store")]
+    pub store: String,
+    /// 快照大小占函数冷启动峰值内存的比例
+    #[serde(default = "default_snapshot_size_ratio")]
+    pub size_ratio: f32,
+    /// 从本地快照恢复的速度 mb/帧
+    #[serde(default = "default_snapshot_local_speed")]
+    pub local_speed: f32,
+    /// 从共享存储恢复的速度 mb/帧
+    #[serde(default = "default_snapshot_shared_speed")]
+    pub shared_speed: f32,
+    /// 恢复时每帧占用的 cpu
+    #[serde(default = "default_snapshot_restore_cpu")]
+    pub restore_cpu: f32,
+    /// 本地快照常驻节点内存的比例，为 0 时快照只占用磁盘
+    #[serde(default)]
+    pub local_mem_ratio: f32,
+}
+
+fn default_snapshot_store() -> String {
+    "local".to_owned()
+}
+
+fn default_snapshot_size_ratio() -> f32 {
+    1.0
+}
+
+fn default_snapshot_local_speed() -> f32 {
+    400.0
+}
+
+fn default_snapshot_shared_speed() -> f32 {
+    100.0
+}
+
+fn default_snapshot_restore_cpu() -> f32 {
+    5.0
+}
+
+impl SnapshotConfig {
+    /// 快照是否保存在节点本地和共享存储中
+    pub fn store_local_shared(&self) -> (bool, bool) {
+        match &*self.store {
+            "local" => (true, false),
+            "shared" => (false, true),
+            "both" => (true, true),
+            s => panic!("not support snapshot store {}, should be local, shared or both", s),
+        }
+    }
+
+    pub fn str(&self) -> String {
+        format!(
+            "{}.r{}.l{}.s{}.c{}.m{}",
+            self.store,
+            self.size_ratio,
+            self.local_speed,
+            self.shared_speed,
+            self.restore_cpu,
+            self.local_mem_ratio
+        )
+    }
+}
+
+/// 函数镜像，每个函数的镜像由一个共享基础层和函数自己的层组成；
+/// 节点在本地磁盘缓存镜像层，冷启动时从镜像仓库节点拉取缺少的层
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ImageConfig {
+    /// 每个节点用于缓存镜像的磁盘大小 mb，超出时按 LRU 淘汰没有容器使用的层
+    #[serde(default = "default_image_disk")]
+    pub disk: f32,
+    /// 共享基础层（如各语言的基础镜像）的个数，每个函数等概率选择一个
+    #[serde(default = "default_image_base_layers")]
+    pub base_layers: usize,
+    /// 基础层大小范围 mb
+    #[serde(default = "default_image_base_size")]
+    pub base_size: (f32, f32),
+    /// 函数层大小范围 mb
+    #[serde(default = "default_image_fn_size")]
+    pub fn_size: (f32, f32),
+    /// 镜像仓库所在的节点，该节点上有所有镜像
+    #[serde(default)]
+    pub registry_node: usize,
+    /// 拉取镜像可以使用的节点间带宽比例
+    #[serde(default = "default_image_pull_bandwidth_ratio")]
+    pub pull_bandwidth_ratio: f32,
+}
+
+fn default_image_disk() -> f32 {
+    4000.0
+}
+
+fn default_image_base_layers() -> usize {
+    3
+}
+
+fn default_image_base_size() -> (f32, f32) {
+    (300.0, 1000.0)
+}
+
+fn default_image_fn_size() -> (f32, f32) {
+    (50.0, 500.0)
+}
+
+fn default_image_pull_bandwidth_ratio() -> f32 {
+    0.05
+}
+
+impl ImageConfig {
+    pub fn str(&self) -> String {
+        format!(
+            "d{}.b{}({}-{}).f({}-{}).r{}.p{}",
+            self.disk,
+            self.base_layers,
+            self.base_size.0,
+            self.base_size.1,
+            self.fn_size.0,
+            self.fn_size.1,
+            self.registry_node,
+            self.pull_bandwidth_ratio
+        )
+    }
+}
+
+/// 节点故障：按 MTBF/MTTR 随机故障，或按脚本在指定帧故障。
+/// 故障节点上的容器、运行中和待处理的任务全部丢失，受影响的函数调用重新等待调度
+#[derive(Serialize, Deserialize, Clone)]
+pub struct FaultConfig {
+    /// 节点平均无故障帧数，每个节点每帧以 1/mtbf 的概率故障，不设置时只有脚本中的故障
+    #[serde(default)]
+    pub mtbf: Option<f32>,
+    /// 随机故障的平均修复帧数，修复时长服从指数分布
+    #[serde(default = "default_fault_mttr")]
+    pub mttr: f32,
+    /// 脚本中的故障
+    #[serde(default)]
+    pub scripted: Vec<ScriptedFault>,
+    /// 每个函数调用因节点故障重试的次数上限，超过后请求失败
+    #[serde(default = "default_fault_max_retries")]
+    pub max_retries: usize,
+}
+
+/// 节点 node 在第 frame 帧故障，frames 帧后恢复
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ScriptedFault {
+    pub node: usize,
+    pub frame: usize,
+    pub frames: usize,
+}
+
+fn default_fault_mttr() -> f32 {
+    20.0
+}
+
+fn default_fault_max_retries() -> usize {
+    3
+}
+
+impl FaultConfig {
+    pub fn str(&self) -> String {
+        let mtbf = self
+            .mtbf
+            .map_or_else(String::new, |m| format!("mtbf{}.mttr{}.", m, self.mttr));
+        let scripted = if self.scripted.is_empty() {
+            String::new()
+        } else {
+            format!(
+                "s({}).",
+                self.scripted
+                    .iter()
+                    .map(|s| format!("n{}f{}+{}", s.node, s.frame, s.frames))
+                    .collect::<Vec<_>>()
+                    .join(",")
+            )
+        };
+        format!("{}{}r{}", mtbf, scripted, self.max_retries)
+    }
+}
+
+/// 请求生成方式
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum WorkloadConfig {
+    /// 回放调用轨迹文件
+    Trace(TraceConfig),
+    /// 回放 workload_record 记录的请求
+    Replay { path: String },
+    /// 闭环负载：users 个用户各自发出请求，请求完成后等待思考时间再发出下一个请求
+    /// - think_time: 平均思考时间（帧）
+    /// - think_time_distr: 思考时间分布，exp 或 const
+    ClosedLoop {
+        users: usize,
+        think_time: f32,
+        #[serde(default = "default_think_time_distr")]
+        think_time_distr: String,
+    },
+    /// 泊松到达，rate 为每帧平均请求数
+    Poisson { rate: f32 },
+    /// 马尔可夫调制泊松过程（突发负载）
+    /// - rates: 各状态下每帧平均请求数
+    /// - switch_probs: 各状态下每帧切换到其它状态的概率，切换时等概率选择其它状态
+    Mmpp {
+        rates: Vec<f32>,
+        switch_probs: Vec<f32>,
+    },
+    /// 正弦昼夜负载，每帧平均请求数为 base + amplitude * sin(2π * (frame + phase) / period)
+    Diurnal {
+        base: f32,
+        amplitude: f32,
+        period: usize,
+        #[serde(default)]
+        phase: usize,
+    },
+    /// 阶梯与尖峰负载
+    /// - steps: [begin_frame, rate]，从 begin_frame 开始每帧平均请求数为 rate
+    /// - spikes: [begin_frame, frame_cnt, rate]，在这段时间内叠加的每帧平均请求数
+    StepSpike {
+        steps: Vec<(usize, f32)>,
+        #[serde(default)]
+        spikes: Vec<(usize, usize, f32)>,
+    },
+}
+
+impl WorkloadConfig {
+    pub fn str(&self) -> String {
+        match self {
+            WorkloadConfig::Trace(trace) => format!(
+                "trace({})",
+                std::path::Path::new(&trace.path)
+                    .file_stem()
+                    .map_or_else(|| trace.path.clone(), |s| s.to_string_lossy().to_string())
+            ),
+            WorkloadConfig::Replay { path } => format!(
+                "replay({})",
+                std::path::Path::new(path)
+                    .file_stem()
+                    .map_or_else(|| path.clone(), |s| s.to_string_lossy().to_string())
+            ),
+            WorkloadConfig::ClosedLoop {
+                users,
+                think_time,
+                think_time_distr,
+            } => format!("closed_loop({}_{}_{})", users, think_time, think_time_distr),
+            WorkloadConfig::Poisson { rate } => format!("poisson({})", rate),
+            WorkloadConfig::Mmpp {
+                rates,
+                switch_probs,
+            } => format!("mmpp({})({})", join_str(rates), join_str(switch_probs)),
+            WorkloadConfig::Diurnal {
+                base,
+                amplitude,
+                period,
+                phase,
+            } => format!("diurnal({}_{}_{}_{})", base, amplitude, period, phase),
+            WorkloadConfig::StepSpike { steps, spikes } => format!(
+                "step_spike({})({})",
+                join_str(&steps.iter().map(|(f, r)| format!("{}_{}", f, r)).collect::<Vec<_>>()),
+                join_str(
+                    &spikes
+                        .iter()
+                        .map(|(f, cnt, r)| format!("{}_{}_{}", f, cnt, r))
+                        .collect::<Vec<_>>()
+                )
+            ),
+        }
+    }
+}
+
+/// 调用轨迹，支持两种格式
+/// - 按分钟统计：Azure Functions 格式的 csv（HashApp,HashFunction,...,1,2,...,1440），
+///   或 jsonl 每行 {"app":..,"counts":[..]}
+/// - 按时间戳记录：csv 包含 timestamp 列（可选 count 列），
+///   或 jsonl 每行 {"app":..,"timestamp":..,"count":..}
+#[derive(Serialize, Deserialize, Clone)]
+pub struct TraceConfig {
+    /// 轨迹文件路径，.csv 或 .jsonl，csv 按逗号切分，不支持带引号的字段
+    pub path: String,
+    /// 轨迹中的应用映射到 dag 的依据：app 或 function
+    #[serde(default = "default_trace_map_by")]
+    pub map_by: String,
+    /// 按分钟统计的轨迹中，一分钟对应的帧数
+    #[serde(default = "default_trace_frames_per_minute")]
+    pub frames_per_minute: usize,
+    /// 按时间戳记录的轨迹中，一帧对应的秒数
+    #[serde(default = "default_trace_secs_per_frame")]
+    pub secs_per_frame: f32,
+    /// 调用次数的缩放系数
+    #[serde(default = "default_trace_scale")]
+    pub scale: f32,
+    /// 轨迹中的应用 -> dag，未指定的应用按出现顺序依次映射
+    #[serde(default)]
+    pub app_map: HashMap<String, usize>,
+}
+
+fn join_str<T: ToString>(v: &[T]) -> String {
+    v.iter().map(|v| v.to_string()).collect::<Vec<_>>().join("_")
+}
+
+fn default_think_time_distr() -> String {
+    "exp".to_owned()
+}
+
+fn default_trace_map_by() -> String {
+    "app".to_owned()
+}
+fn default_trace_frames_per_minute() -> usize {
+    60
+}
+fn default_trace_secs_per_frame() -> f32 {
+    1.0
+}
+fn default_trace_scale() -> f32 {
+    1.0
 }
 
 impl Config {
@@ -87,6 +865,34 @@ impl Config {
         false
     }
 
+    pub fn dag_type_chain(&self) -> bool {
+        if &*self.dag_type == "chain" {
+            return true;
+        }
+        false
+    }
+
+    pub fn dag_type_layered(&self) -> bool {
+        if &*self.dag_type == "layered" {
+            return true;
+        }
+        false
+    }
+
+    pub fn dag_type_series_parallel(&self) -> bool {
+        if &*self.dag_type == "series_parallel" {
+            return true;
+        }
+        false
+    }
+
+    pub fn dag_type_dynamic_map(&self) -> bool {
+        if &*self.dag_type == "dynamic_map" {
+            return true;
+        }
+        false
+    }
+
     pub fn dag_type_mix(&self) -> bool {
         if &*self.dag_type == "mix" {
             return true;
@@ -94,6 +900,45 @@ impl Config {
         false
     }
 
+    pub fn dag_gen_conf(&self) -> DagGenConfig {
+        self.dag_gen.clone().unwrap_or_default()
+    }
+
+    // 应用是否按各自的请求速率生成请求，配置了应用描述文件时按文件中的应用判断
+    pub fn app_rate_set(&self) -> bool {
+        match &self.app_spec {
+            Some(path) => AppSpec::load(path).rate_set(),
+            None => self.app_types_rate(),
+        }
+    }
+
+    // 应用类型是否按各自的请求速率生成请求，需要所有应用类型都设置 rate 或都不设置
+    fn app_types_rate(&self) -> bool {
+        let rate_cnt = self.app_types.iter().filter(|a| a.rate.is_some()).count();
+        assert!(
+            rate_cnt == 0 || rate_cnt == self.app_types.len(),
+            "app_types should set rate for all or none of the app types, {} of {} set",
+            rate_cnt,
+            self.app_types.len()
+        );
+        rate_cnt > 0
+    }
+
+    // 集群描述，按 cluster_spec、cluster、node_cnt 的顺序确定
+    pub fn cluster_conf(&self) -> ClusterSpec {
+        assert!(
+            self.cluster.is_none() || self.cluster_spec.is_none(),
+            "cluster and cluster_spec should not be set at the same time"
+        );
+        if let Some(path) = &self.cluster_spec {
+            ClusterSpec::load(path)
+        } else if let Some(cluster) = &self.cluster {
+            cluster.clone()
+        } else {
+            ClusterSpec::uniform(self.node_cnt)
+        }
+    }
+
     pub fn fntype_cpu(&self) -> bool {
         if &*self.fn_type == "cpu" {
             return true;
@@ -156,13 +1001,107 @@ impl Config {
         let scdown = self.mech.scale_down_exec_conf();
         let scup = self.mech.scale_up_exec_conf();
         let sche = self.mech.sche_conf();
+        let workload = self
+            .workload
+            .as_ref()
+            .map_or_else(String::new, |w| format!(".wl{}", w.str()));
+        let app_types = if self.app_types.is_empty() {
+            String::new()
+        } else {
+            format!(
+                ".app({})",
+                self.app_types
+                    .iter()
+                    .map(|a| a.str())
+                    .collect::<Vec<_>>()
+                    .join(",")
+            )
+        };
+        let app_spec = self.app_spec.as_ref().map_or_else(String::new, |p| {
+            format!(
+                ".spec({})",
+                std::path::Path::new(p)
+                    .file_stem()
+                    .map_or_else(|| p.clone(), |s| s.to_string_lossy().to_string())
+            )
+        });
+        let dag_gen = self
+            .dag_gen
+            .as_ref()
+            .map_or_else(String::new, |d| format!(".dg({})", d.str()));
+        let admission = self
+            .admission
+            .as_ref()
+            .map_or_else(String::new, |a| format!(".adm({})", a.str()));
+        let req_timeout = self
+            .req_timeout
+            .map_or_else(String::new, |t| format!(".to{}", t));
+        let cold_start_phases = self.cold_start_phases.as_ref().map_or_else(String::new, |ps| {
+            format!(
+                ".csp({})",
+                ps.iter().map(|p| p.str()).collect::<Vec<_>>().join(",")
+            )
+        });
+        let fn_type_weights = self.fn_type_weights.as_ref().map_or_else(String::new, |ws| {
+            format!(
+                "({})",
+                ws.iter()
+                    .map(|(a, w)| format!("{}{}", a.str(), w))
+                    .collect::<Vec<_>>()
+                    .join(",")
+            )
+        });
+        let exec_var = self
+            .exec_var
+            .as_ref()
+            .map_or_else(String::new, |e| format!(".ev({})", e.str()));
+        let replicas = if self.max_replicas_per_node == 1 {
+            String::new()
+        } else {
+            format!(".rpn{}", self.max_replicas_per_node)
+        };
+        let concurrency = self
+            .container_concurrency
+            .map_or_else(String::new, |c| format!(".cc{}", c));
+        let stem_cell = self
+            .stem_cell
+            .as_ref()
+            .map_or_else(String::new, |s| format!(".sc({})", s.str()));
+        let snapshot = self
+            .snapshot
+            .as_ref()
+            .map_or_else(String::new, |s| format!(".ss({})", s.str()));
+        let image = self
+            .image
+            .as_ref()
+            .map_or_else(String::new, |i| format!(".img({})", i.str()));
+        let cluster = if let Some(p) = &self.cluster_spec {
+            format!(
+                ".cluster({})",
+                std::path::Path::new(p)
+                    .file_stem()
+                    .map_or_else(|| p.clone(), |s| s.to_string_lossy().to_string())
+            )
+        } else if let Some(c) = &self.cluster {
+            format!(".cl({})", c.str())
+        } else if self.node_cnt != NODE_CNT {
+            format!(".nc{}", self.node_cnt)
+        } else {
+            String::new()
+        };
+        let fault = self
+            .fault
+            .as_ref()
+            .map_or_else(String::new, |f| format!(".fault({})", f.str()));
         format!(
-            "sd{}.rf{}.dt{}.cs{}.ft{}.scl({},{})({},{})({},{}).scd({},{})",
+            "sd{}.rf{}.dt{}.cs{}{}.ft{}{}.scl({},{})({},{})({},{}).scd({},{}){}{}{}{}{}{}{}{}{}{}{}{}{}{}",
             self.rand_seed,
             self.request_freq,
             self.dag_type,
             self.cold_start,
+            cold_start_phases,
             self.fn_type,
+            fn_type_weights,
             scnum.0,
             scnum.1,
             scdown.0,
@@ -170,7 +1109,49 @@ impl Config {
             scup.0,
             scup.1,
             sche.0,
-            sche.1
+            sche.1,
+            workload,
+            app_types,
+            app_spec,
+            dag_gen,
+            admission,
+            req_timeout,
+            exec_var,
+            replicas,
+            concurrency,
+            stem_cell,
+            snapshot,
+            image,
+            cluster,
+            fault
         )
     }
 }
+
+// 测试用的配置，extra 中的字段覆盖默认值
+#[cfg(test)]
+pub fn test_config(extra: Value) -> Config {
+    let mut mech = crate::mechanism_conf::ModuleMechConf::new().0;
+    for (sub, name) in [
+        (&mut mech.mech_type, "no_scale"),
+        (&mut mech.scale_num, "no"),
+        (&mut mech.scale_down_exec, "default"),
+        (&mut mech.scale_up_exec, "no"),
+        (&mut mech.sche, "random"),
+    ] {
+        sub.insert(name.to_owned(), Some(String::new()));
+    }
+    let mut v = serde_json::json!({
+        "rand_seed": "hello",
+        "request_freq": "low",
+        "dag_type": "single",
+        "cold_start": "high",
+        "fn_type": "cpu",
+        "mech": mech,
+        "no_log": true,
+    });
+    v.as_object_mut()
+        .unwrap()
+        .extend(extra.as_object().unwrap().clone());
+    serde_json::from_value(v).unwrap()
+}
@@ -29,7 +29,7 @@ pub fn start_gc() {
                     if now > Duration::from_secs(60) + env.recent_use_time {
                         let key = env.help.config().str();
                         log::warn!("gc env {}", key);
-                        env.help.metric_record().flush(&env);
+                        env.flush_records();
                         to_remove.push(key);
                     }
                 }
@@ -0,0 +1,377 @@
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    fmt::Write as _,
+    fs,
+    io::Write,
+    path::Path,
+};
+
+use daggy::petgraph::visit::{EdgeRef, Topo};
+use serde::Serialize;
+
+use crate::{
+    cold_start::ColdStartPhase,
+    config::{FanOutConfig, VarDistr},
+    fn_dag::{DagId, FnDAG, FnId},
+    fn_type::FnArchetype,
+    image::FnImage,
+    node::NodeId,
+    request::{ReqId, Request},
+    sim_env::SimEnv,
+};
+
+/// 导出的函数画像
+#[derive(Serialize)]
+struct FnExport {
+    fn_id: FnId,
+    cpu: f32,
+    mem: f32,
+    out_put_size: f32,
+    cold_start_time: usize,
+    cold_start_container_mem_use: f32,
+    cold_start_container_cpu_use: f32,
+    cold_start_phases: Vec<ColdStartPhase>,
+    archetype: Option<FnArchetype>,
+    cpu_distr: Option<VarDistr>,
+    out_distr: Option<VarDistr>,
+    input_corr: f32,
+    concurrency: Option<usize>,
+    image: Option<FnImage>,
+    // 被多个应用共享时为 true
+    shared: bool,
+    // 选择函数，每次请求只触发一条后继边
+    choice: bool,
+    // 动态 map 函数的并行实例数分布
+    fan_out: Option<FanOutConfig>,
+}
+
+/// 导出的函数间的边，data_size 为传输的数据量
+#[derive(Serialize)]
+struct EdgeExport {
+    from: FnId,
+    to: FnId,
+    data_size: f32,
+    prob: f32,
+}
+
+/// 导出的应用 DAG
+#[derive(Serialize)]
+struct DagExport {
+    dag_i: DagId,
+    weight: f32,
+    req_rate: Option<f32>,
+    slo: usize,
+    begin_fn: FnId,
+    fns: Vec<FnExport>,
+    edges: Vec<EdgeExport>,
+}
+
+/// 请求中一个函数的执行过程
+#[derive(Serialize)]
+struct FnTimeline {
+    fn_id: FnId,
+    // 执行节点 -> 该节点上的实例数
+    nodes: BTreeMap<NodeId, usize>,
+    sche_frame: Option<usize>,
+    done_frame: Option<usize>,
+    // 在容器前排队的帧数
+    queue_frames: usize,
+    // 因节点故障重新调度的次数
+    retries: usize,
+    // 本次执行的运算量倍数和输出数据量倍数
+    exec_factors: (f32, f32),
+}
+
+/// 导出的节点
+#[derive(Serialize)]
+struct NodeExport {
+    node_id: NodeId,
+    cpu: f32,
+    mem: f32,
+    speed: f32,
+    labels: BTreeMap<String, String>,
+}
+
+/// 导出的快照位置
+#[derive(Serialize)]
+struct SnapshotsExport {
+    // 共享存储中有快照的函数
+    shared: BTreeSet<FnId>,
+    // 节点 -> 节点上有本地快照的函数
+    local: BTreeMap<NodeId, BTreeSet<FnId>>,
+}
+
+/// 导出的请求执行过程，requests.jsonl 中每行一个
+#[derive(Serialize)]
+struct ReqTimeline {
+    req_id: ReqId,
+    dag_i: DagId,
+    begin_frame: usize,
+    end_frame: usize,
+    deadline: usize,
+    input_size: f32,
+    fns: Vec<FnTimeline>,
+    // 条件DAG中本次请求实际触发的边
+    edges: Vec<(FnId, FnId)>,
+}
+
+impl FnDAG {
+    fn export(&self, env: &SimEnv) -> DagExport {
+        let mut fns = vec![];
+        let mut walker = Topo::new(&self.dag_inner);
+        while let Some(gi) = walker.next(&self.dag_inner) {
+            let func = env.func(self.dag_inner[gi]);
+            fns.push(FnExport {
+                fn_id: func.fn_id,
+                cpu: func.cpu,
+                mem: func.mem,
+                out_put_size: func.out_put_size,
+                cold_start_time: func.cold_start_time,
+                cold_start_container_mem_use: func.cold_start_container_mem_use,
+                cold_start_container_cpu_use: func.cold_start_container_cpu_use,
+                cold_start_phases: func.cold_start_phases.clone(),
+                archetype: func.archetype,
+                cpu_distr: func.cpu_distr.clone(),
+                out_distr: func.out_distr.clone(),
+                input_corr: func.input_corr,
+                concurrency: func.concurrency,
+                image: func.image.clone(),
+                shared: func.dags.len() > 1,
+                choice: self.choice_fns.contains(&gi),
+                fan_out: self.fan_out.get(&gi).cloned(),
+            });
+        }
+        let edges = self
+            .dag_inner
+            .graph()
+            .edge_references()
+            .map(|e| EdgeExport {
+                from: self.dag_inner[e.source()],
+                to: self.dag_inner[e.target()],
+                data_size: *e.weight(),
+                prob: self.edge_prob(e.id()),
+            })
+            .collect();
+        DagExport {
+            dag_i: self.dag_i,
+            weight: self.weight,
+            req_rate: self.req_rate,
+            slo: self.slo,
+            begin_fn: self.dag_inner[self.begin_fn_g_i],
+            fns,
+            edges,
+        }
+    }
+
+    // 生成 Graphviz DOT，节点标注函数画像，边标注数据量和触发概率
+    pub fn to_dot(&self, env: &SimEnv) -> String {
+        let dag = self.export(env);
+        let mut s = format!("digraph dag{} {{\n    node [shape=box];\n", dag.dag_i);
+        for f in &dag.fns {
+            let mut label = format!(
+                "fn{}\\ncpu {:.1} mem {:.1}\\nout {:.1}\\ncold {}f",
+                f.fn_id, f.cpu, f.mem, f.out_put_size, f.cold_start_time
+            );
+            if let Some(archetype) = f.archetype {
+                label += &format!("\\n{}", archetype.str());
+            }
+            if let Some(fan_out) = &f.fan_out {
+                label += &format!("\\nmap {}", fan_out.str());
+            }
+            let mut attrs = format!("label=\"{}\"", label);
+            if f.choice {
+                attrs += ", shape=diamond";
+            }
+            if f.shared {
+                attrs += ", style=filled, fillcolor=lightgrey";
+            }
+            if f.fn_id == dag.begin_fn {
+                attrs += ", penwidth=2";
+            }
+            writeln!(s, "    f{} [{}];", f.fn_id, attrs).unwrap();
+        }
+        for e in &dag.edges {
+            let mut label = format!("{:.1}", e.data_size);
+            let mut style = "";
+            if e.prob < 1.0 {
+                label += &format!(" p={:.2}", e.prob);
+                style = ", style=dashed";
+            }
+            writeln!(s, "    f{} -> f{} [label=\"{}\"{}];", e.from, e.to, label, style).unwrap();
+        }
+        s += "}\n";
+        s
+    }
+
+    pub fn to_json(&self, env: &SimEnv) -> String {
+        serde_json::to_string_pretty(&self.export(env)).unwrap()
+    }
+}
+
+impl Request {
+    fn timeline(&self, env: &SimEnv) -> ReqTimeline {
+        let dag = env.dag(self.dag_i);
+        let mut fns = vec![];
+        let mut edges = vec![];
+        let mut walker = Topo::new(&dag.dag_inner);
+        while let Some(gi) = walker.next(&dag.dag_inner) {
+            let fnid = dag.dag_inner[gi];
+            if !self.fn_active(fnid) {
+                continue;
+            }
+            fns.push(FnTimeline {
+                fn_id: fnid,
+                nodes: self.fn_nodes(fnid).into_iter().collect(),
+                sche_frame: self.fn_sche_frame.get(&fnid).copied(),
+                done_frame: self.done_fns.get(&fnid).copied(),
+                queue_frames: self.fn_queue_frames.get(&fnid).copied().unwrap_or(0),
+                retries: self.fn_retries.get(&fnid).copied().unwrap_or(0),
+                exec_factors: env.fn_exec_factors(self, fnid),
+            });
+            for p in self.parent_fns(env, fnid) {
+                edges.push((p, fnid));
+            }
+        }
+        ReqTimeline {
+            req_id: self.req_id,
+            dag_i: self.dag_i,
+            begin_frame: self.begin_frame,
+            end_frame: self.end_frame,
+            deadline: self.deadline,
+            input_size: self.input_size,
+            fns,
+            edges,
+        }
+    }
+
+    // 生成 Graphviz DOT，按执行节点分组，函数标注调度和完成的帧
+    pub fn to_dot(&self, env: &SimEnv) -> String {
+        let tl = self.timeline(env);
+        let mut s = format!(
+            "digraph req{} {{\n    label=\"req{} dag{} frame {}-{} deadline {}\";\n    node [shape=box];\n",
+            tl.req_id, tl.req_id, tl.dag_i, tl.begin_frame, tl.end_frame, tl.deadline
+        );
+        let mut node_fns: BTreeMap<NodeId, Vec<(FnId, usize)>> = BTreeMap::new();
+        let mut fn_nodes: HashMap<FnId, Vec<NodeId>> = HashMap::new();
+        for f in &tl.fns {
+            for (&n, &cnt) in &f.nodes {
+                node_fns.entry(n).or_default().push((f.fn_id, cnt));
+                fn_nodes.entry(f.fn_id).or_default().push(n);
+            }
+        }
+        let frame_str = |frame: Option<usize>| frame.map_or("-".to_owned(), |f| f.to_string());
+        for (n, fns) in &node_fns {
+            writeln!(s, "    subgraph cluster_node{} {{\n        label=\"node{}\";", n, n).unwrap();
+            for &(fnid, cnt) in fns {
+                let f = tl.fns.iter().find(|f| f.fn_id == fnid).unwrap();
+                let mut label = format!("fn{}", fnid);
+                if cnt > 1 {
+                    label += &format!(" x{}", cnt);
+                }
+                label += &format!(
+                    "\\nsche {} done {}",
+                    frame_str(f.sche_frame),
+                    frame_str(f.done_frame)
+                );
+                writeln!(s, "        f{}_n{} [label=\"{}\"];", fnid, n, label).unwrap();
+            }
+            s += "    }\n";
+        }
+        for (p, c) in &tl.edges {
+            let (Some(pnodes), Some(cnodes)) = (fn_nodes.get(p), fn_nodes.get(c)) else {
+                continue;
+            };
+            for pn in pnodes {
+                for cn in cnodes {
+                    writeln!(s, "    f{}_n{} -> f{}_n{};", p, pn, c, cn).unwrap();
+                }
+            }
+        }
+        s += "}\n";
+        s
+    }
+}
+
+fn write_file(path: &Path, content: &str) {
+    fs::write(path, content)
+        .unwrap_or_else(|e| panic!("write export file {} failed: {}", path.display(), e));
+}
+
+impl SimEnv {
+    // 导出所有应用的 DAG：dag{i}.dot 和 dag{i}.json
+    pub fn export_dags(&self) {
+        let Some(export) = &self.help.config().export else {
+            return;
+        };
+        let dir = Path::new(&export.dir);
+        fs::create_dir_all(dir).unwrap();
+        for dag in self.core.dags().iter() {
+            write_file(&dir.join(format!("dag{}.dot", dag.dag_i)), &dag.to_dot(self));
+            write_file(&dir.join(format!("dag{}.json", dag.dag_i)), &dag.to_json(self));
+        }
+        log::info!("export {} dags to {}", self.core.dags().len(), export.dir);
+    }
+
+    // 导出集群的节点到 cluster.json
+    pub fn export_cluster(&self) {
+        let Some(export) = &self.help.config().export else {
+            return;
+        };
+        let dir = Path::new(&export.dir);
+        fs::create_dir_all(dir).unwrap();
+        let nodes: Vec<NodeExport> = self
+            .core
+            .nodes()
+            .iter()
+            .map(|n| NodeExport {
+                node_id: n.node_id(),
+                cpu: n.rsc_limit.cpu,
+                mem: n.rsc_limit.mem,
+                speed: n.speed,
+                labels: n.labels.clone(),
+            })
+            .collect();
+        write_file(
+            &dir.join("cluster.json"),
+            &serde_json::to_string_pretty(&nodes).unwrap(),
+        );
+    }
+
+    // 导出已完成请求的执行过程到 requests.jsonl，前 req_dot_cnt 个请求另外导出 req{id}.dot
+    pub fn export_requests(&self) {
+        let Some(export) = &self.help.config().export else {
+            return;
+        };
+        let dir = Path::new(&export.dir);
+        fs::create_dir_all(dir).unwrap();
+        let reqs = self.core.done_requests();
+        let path = dir.join("requests.jsonl");
+        let mut file = fs::File::create(&path)
+            .unwrap_or_else(|e| panic!("create export file {} failed: {}", path.display(), e));
+        for req in reqs.iter() {
+            writeln!(file, "{}", serde_json::to_string(&req.timeline(self)).unwrap()).unwrap();
+        }
+        for req in reqs.iter().take(export.req_dot_cnt) {
+            write_file(&dir.join(format!("req{}.dot", req.req_id)), &req.to_dot(self));
+        }
+        log::info!("export {} requests to {}", reqs.len(), export.dir);
+    }
+
+    // 导出模拟结束时快照所在的位置到 snapshots.json
+    pub fn export_snapshots(&self) {
+        let (Some(export), Some(_)) = (&self.help.config().export, &self.help.config().snapshot)
+        else {
+            return;
+        };
+        let dir = Path::new(&export.dir);
+        fs::create_dir_all(dir).unwrap();
+        let snapshots = SnapshotsExport {
+            shared: self.core.shared_snapshots().clone(),
+            local: self.local_snapshots().into_iter().enumerate().collect(),
+        };
+        write_file(
+            &dir.join("snapshots.json"),
+            &serde_json::to_string_pretty(&snapshots).unwrap(),
+        );
+    }
+}
@@ -0,0 +1,183 @@
+use daggy::Walker;
+
+use crate::{
+    fn_dag::FnId,
+    node::{Node, NodeId},
+    request::{ReqId, Request},
+    sim_env::SimEnv,
+};
+
+impl Node {
+    pub fn is_down(&self) -> bool {
+        self.down_until.is_some()
+    }
+
+    // 请求的函数在该节点上的任务已完成的运算量
+    fn task_done_work(&self, req: &Request, fnid: FnId, env: &SimEnv) -> f32 {
+        let total_calc = env.func(fnid).cpu * env.fn_exec_factors(req, fnid).0;
+        self.fn_containers
+            .borrow()
+            .iter()
+            .filter(|((f, _), _)| *f == fnid)
+            .filter_map(|(_, c)| c.req_fn_state.get(&req.req_id))
+            .map(|t| (total_calc - t.left_calc.max(0.0)) * t.instances as f32)
+            .sum()
+    }
+}
+
+impl SimEnv {
+    /// 从 nid 开始依次查找第一个没有故障的节点，按哈希等方式选定节点的调度器用它跳过故障节点；
+    /// 所有节点都故障时返回 nid
+    pub fn first_up_node_from(&self, nid: NodeId) -> NodeId {
+        let node_cnt = self.node_cnt();
+        (0..node_cnt)
+            .map(|i| (nid + i) % node_cnt)
+            .find(|&n| !self.node(n).is_down())
+            .unwrap_or(nid)
+    }
+
+    /// 模拟节点故障和恢复：先恢复到期的节点，再注入脚本中和按 MTBF 随机产生的故障
+    pub fn sim_node_faults(&self) {
+        let Some(fault) = self.help.config().fault.as_ref() else {
+            return;
+        };
+        let frame = self.current_frame();
+        for n in self.core.nodes_mut().iter_mut() {
+            if n.down_until.is_some_and(|f| f <= frame) {
+                n.down_until = None;
+                *n.unready_mem_mut() = n.snapshots_mem(self);
+                log::info!("node {} recovered at frame {}", n.node_id(), frame);
+            }
+        }
+
+        let mut failures: Vec<(NodeId, usize)> = fault
+            .scripted
+            .iter()
+            .filter(|s| s.frame == frame)
+            .map(|s| (s.node, s.frames))
+            .collect();
+        if let Some(mtbf) = fault.mtbf {
+            for nid in 0..self.node_cnt() {
+                if self.fault_rand_f(0.0, 1.0) < 1.0 / mtbf {
+                    // 修复时长服从均值为 mttr 的指数分布，至少 1 帧
+                    let u = self.fault_rand_f(f32::EPSILON, 1.0);
+                    let frames = ((-u.ln() * fault.mttr).ceil() as usize).max(1);
+                    failures.push((nid, frames));
+                }
+            }
+        }
+        for (nid, frames) in failures {
+            assert!(
+                nid < self.node_cnt(),
+                "fault node {} should be less than node count {}",
+                nid,
+                self.node_cnt()
+            );
+            if !self.node(nid).is_down() {
+                self.node_fail(nid, frames);
+            }
+        }
+    }
+
+    // 节点故障 frames 帧：容器（包括其中运行和排队的任务）、待处理的任务和预热容器全部丢失，
+    // 受影响的函数调用重新等待调度。已完成函数的输出视为已经持久化，仍从原节点传输
+    fn node_fail(&self, nid: NodeId, frames: usize) {
+        let frame = self.current_frame();
+        let mut lost_tasks = self.node(nid).take_pending_tasks();
+        let mut lost_work = 0.0;
+        {
+            let mut nodes = self.core.nodes_mut();
+            let n = &mut nodes[nid];
+            let containers: Vec<(FnId, usize)> = n.fn_containers.borrow().keys().copied().collect();
+            for (fnid, replica) in containers {
+                let req_ids: Vec<ReqId> = n
+                    .container(fnid, replica)
+                    .unwrap()
+                    .req_fn_state
+                    .keys()
+                    .copied()
+                    .collect();
+                for req_id in req_ids {
+                    lost_work += n.task_done_work(&self.request(req_id), fnid, self);
+                    lost_tasks.push((req_id, fnid));
+                }
+                n.try_unload_container(fnid, Some(replica), self);
+            }
+            n.stem_cells.borrow_mut().clear();
+            n.down_until = Some(frame + frames);
+            *n.unready_mem_mut() = n.rsc_limit.mem;
+        }
+        log::info!(
+            "node {} failed at frame {} for {} frames, lost {} tasks",
+            nid,
+            frame,
+            frames,
+            lost_tasks.len()
+        );
+        self.help
+            .metric_mut()
+            .add_node_failure(lost_tasks.len(), lost_work);
+
+        lost_tasks.sort();
+        lost_tasks.dedup();
+        for (req_id, fnid) in lost_tasks {
+            self.fault_retry_fn(req_id, fnid);
+        }
+    }
+
+    // 重新调度请求中丢失的函数调用，超过重试次数时请求失败
+    fn fault_retry_fn(&self, req_id: ReqId, fnid: FnId) {
+        if !self.core.requests().contains_key(&req_id) {
+            return;
+        }
+        let max_retries = self.help.config().fault.as_ref().unwrap().max_retries;
+        let retries = {
+            let mut req = self.request_mut(req_id);
+            let retries = req.fn_retries.entry(fnid).or_insert(0);
+            *retries += 1;
+            *retries
+        };
+        if retries > max_retries {
+            log::info!("req {} fn {} exceeds max retries, request failed", req_id, fnid);
+            self.abort_request(req_id);
+            self.help.metric_mut().add_failed_request();
+            return;
+        }
+        self.fault_requeue_fn(req_id, fnid);
+    }
+
+    // 移除函数在各节点上的剩余任务，使其重新等待调度；
+    // 已调度但未完成的后继函数的数据来源随之改变，一并重新等待调度
+    fn fault_requeue_fn(&self, req_id: ReqId, fnid: FnId) {
+        let mut to_requeue = vec![fnid];
+        let mut lost_work = 0.0;
+        while let Some(fnid) = to_requeue.pop() {
+            let req = self.request(req_id);
+            if !req.fn_node.contains_key(&fnid) || req.done_fns.contains_key(&fnid) {
+                continue;
+            }
+            for (nid, _) in req.fn_nodes(fnid) {
+                let node = self.node(nid);
+                lost_work += node.task_done_work(&req, fnid, self);
+                node.remove_task(req_id, fnid);
+            }
+            let dag = self.dag(req.dag_i);
+            let children: Vec<FnId> = dag
+                .dag_inner
+                .children(dag.graph_i(fnid))
+                .iter(&dag.dag_inner)
+                .map(|(_, gi)| dag.dag_inner[gi])
+                .collect();
+            drop(dag);
+            drop(req);
+            to_requeue.extend(children);
+
+            let mut req = self.request_mut(req_id);
+            req.fn_node.remove(&fnid);
+            req.fn_instance_nodes.remove(&fnid);
+            req.fn_left_instances.remove(&fnid);
+            req.fn_sche_frame.remove(&fnid);
+        }
+        self.help.metric_mut().add_lost_work(lost_work);
+    }
+}
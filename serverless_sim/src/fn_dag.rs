@@ -1,24 +1,32 @@
 use std::{
     cell::{Ref, RefMut},
-    collections::{HashMap, HashSet, VecDeque},
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque},
 };
 
 use daggy::{
     petgraph::visit::{Topo, Visitable},
-    Dag, NodeIndex, Walker,
+    Dag, EdgeIndex, NodeIndex, Walker,
 };
 use enum_as_inner::EnumAsInner;
+use rand::Rng;
 
 use crate::{
-    config::APPConfig,
+    app_spec::{AppSpec, AppSpecApp, FnSpec},
+    cold_start::{self, ColdStartPhase, ColdStartPhaseKind},
+    config::{APPConfig, DagGenConfig, FanOutConfig, VarDistr},
+    fn_type::FnArchetype,
+    image::FnImage,
     node::{Node, NodeId},
     request::{ReqId, Request},
     sim_env::SimEnv,
-    util, CONTAINER_BASIC_MEM,
+    util, CONTAINER_BASIC_MEM, SLO_FRAMES_PER_FN,
 };
 
 pub type FnId = usize;
 
+// 同一节点上同一函数的容器副本编号
+pub type ReplicaId = usize;
+
 pub type DagId = usize;
 
 pub type FnDagInner = Dag<FnId, f32>;
@@ -27,6 +35,20 @@ pub struct FnDAG {
     pub dag_i: DagId,
     pub begin_fn_g_i: NodeIndex,
     pub dag_inner: FnDagInner,
+    // 应用被选中的权重
+    pub weight: f32,
+    // 应用每帧的平均请求数，设置后按应用各自的速率生成请求
+    pub req_rate: Option<f32>,
+    // 端到端延迟 SLO（帧）
+    pub slo: usize,
+    // 条件边的触发概率，不在表中的边总是触发
+    pub edge_probs: HashMap<EdgeIndex, f32>,
+    // 选择函数：每次请求只触发一条后继边，按边的触发概率加权选择
+    pub choice_fns: HashSet<NodeIndex>,
+    // 动态 map 函数：每次请求按分布抽取并行实例数
+    pub fan_out: BTreeMap<NodeIndex, FanOutConfig>,
+    // 函数在这个DAG中的位置，同一个函数可以被多个DAG共享
+    pub fn_graph_i: HashMap<FnId, NodeIndex>,
 }
 
 impl FnDAG {
@@ -37,45 +59,324 @@ impl FnDAG {
         // 加入起始函数
         let begin = dag.add_node(begin_fn);
 
-        // 设置这个函数实例的DAGid以及在这个DAG中的位置
-        env.func_mut(begin_fn)
-            .setup_after_insert_into_dag(dag_i, begin);
-
-        Self {
+        let mut new = Self {
             dag_i, 
             begin_fn_g_i: begin,
             dag_inner: dag,
+            weight: 1.0,
+            req_rate: None,
+            slo: 0,
+            edge_probs: HashMap::new(),
+            choice_fns: HashSet::new(),
+            fan_out: BTreeMap::new(),
+            fn_graph_i: HashMap::new(),
+        };
+        // 设置这个函数实例的DAGid以及在这个DAG中的位置
+        new.setup_fn_after_insert(env, begin_fn, begin);
+        new
+    }
+
+    // 记录函数在这个DAG中的位置，一个函数在同一个DAG中只能出现一次
+    fn setup_fn_after_insert(&mut self, env: &SimEnv, fnid: FnId, graph_i: NodeIndex) {
+        let old = self.fn_graph_i.insert(fnid, graph_i);
+        assert!(old.is_none(), "fn {} appears twice in dag {}", fnid, self.dag_i);
+        env.func_mut(fnid).dags.insert(self.dag_i);
+    }
+
+    // 函数在这个DAG中的位置
+    pub fn graph_i(&self, fnid: FnId) -> NodeIndex {
+        *self
+            .fn_graph_i
+            .get(&fnid)
+            .unwrap_or_else(|| panic!("fn {} not in dag {}", fnid, self.dag_i))
+    }
+
+    // 函数在这个DAG中的前驱函数
+    pub fn parent_fns(&self, fnid: FnId) -> Vec<FnId> {
+        let ps = self.dag_inner.parents(self.graph_i(fnid));
+        ps.iter(&self.dag_inner)
+            .map(|(_edge, graph_i)| self.dag_inner[graph_i])
+            .collect()
+    }
+
+    // 是否含有条件分支，不含时每个请求执行DAG中的所有函数
+    pub fn is_conditional(&self) -> bool {
+        !self.edge_probs.is_empty() || !self.choice_fns.is_empty()
+    }
+
+    pub fn edge_prob(&self, e: EdgeIndex) -> f32 {
+        self.edge_probs.get(&e).copied().unwrap_or(1.0)
+    }
+
+    // 为一个请求抽取实际执行的函数：起始函数总是执行，执行了的函数按边的概率触发后继，
+    // 至少有一条入边被触发的函数才执行。返回 函数 -> 触发了它的前驱函数
+    pub fn draw_active_fns(&self, rng: &mut impl Rng) -> HashMap<FnId, Vec<FnId>> {
+        let g = &self.dag_inner;
+        // 起始函数没有前驱
+        let mut active: HashMap<NodeIndex, Vec<FnId>> = g
+            .graph()
+            .node_indices()
+            .filter(|&gi| g.parents(gi).iter(g).next().is_none())
+            .map(|gi| (gi, vec![]))
+            .collect();
+        let mut iter = Topo::new(g);
+        while let Some(gi) = iter.next(g) {
+            if !active.contains_key(&gi) {
+                continue;
+            }
+
+            let children: Vec<(EdgeIndex, NodeIndex)> = g.children(gi).iter(g).collect();
+            let fired: Vec<NodeIndex> = if self.choice_fns.contains(&gi) && !children.is_empty() {
+                let total: f32 = children.iter().map(|(e, _)| self.edge_prob(*e)).sum();
+                let mut r = if total > 0.0 {
+                    rng.gen_range(0.0..total)
+                } else {
+                    0.0
+                };
+                let mut chosen = children.last().unwrap().1;
+                for &(e, c) in &children {
+                    if r < self.edge_prob(e) {
+                        chosen = c;
+                        break;
+                    }
+                    r -= self.edge_prob(e);
+                }
+                vec![chosen]
+            } else {
+                children
+                    .iter()
+                    .filter(|(e, _)| {
+                        let p = self.edge_prob(*e);
+                        p >= 1.0 || rng.gen_range(0.0..1.0) < p
+                    })
+                    .map(|(_, c)| *c)
+                    .collect()
+            };
+            for c in fired {
+                active.entry(c).or_default().push(g[gi]);
+            }
+        }
+        active.into_iter().map(|(gi, ps)| (g[gi], ps)).collect()
+    }
+
+    // 以 choice_prob 的概率将有多个后继的函数设为选择函数，后继等概率被选中
+    fn rand_choice_fns(&mut self, env: &SimEnv, choice_prob: f32) {
+        if choice_prob <= 0.0 {
+            return;
+        }
+        for gi in self.dag_inner.graph().node_indices() {
+            if self.dag_inner.children(gi).iter(&self.dag_inner).count() > 1
+                && env.env_rand_f(0.0, 1.0) < choice_prob
+            {
+                self.choice_fns.insert(gi);
+            }
         }
     }
 
     // 单函数DAG
     pub fn instance_single_fn(dag_i: DagId, env: &SimEnv) -> FnDAG {
-        let begin_fn: FnId = env.fn_gen_rand_fn();
+        let begin_fn: FnId = env.fn_gen_dag_fn(None);
         let dag = FnDAG::new(begin_fn, dag_i, env);
         dag
     }
 
+    // 链式DAG，len 个函数依次调用
+    pub fn instance_chain(dag_i: DagId, env: &SimEnv, len: usize) -> FnDAG {
+        let begin_fn = env.fn_gen_dag_fn(None);
+        let mut dag = FnDAG::new(begin_fn, dag_i, env);
+
+        let mut prev_fn = begin_fn;
+        let mut prev_g_i = dag.begin_fn_g_i;
+        for _ in 1..len {
+            let next = env.fn_gen_dag_fn(Some(&dag));
+            // 前一个函数的输出数据传给下一个函数
+            let (_, next_i) =
+                dag.dag_inner
+                    .add_child(prev_g_i, env.func(prev_fn).out_put_size, next);
+            dag.setup_fn_after_insert(env, next, next_i);
+            prev_fn = next;
+            prev_g_i = next_i;
+        }
+
+        dag
+    }
+
+    // 新建或复用一个共享函数加入DAG，返回它的节点索引
+    fn add_rand_fn(&mut self, env: &SimEnv) -> NodeIndex {
+        let fnid = env.fn_gen_dag_fn(Some(self));
+        let g_i = self.dag_inner.add_node(fnid);
+        self.setup_fn_after_insert(env, fnid, g_i);
+        g_i
+    }
+
+    // 边上的数据量，未配置范围时为前驱函数的输出数据量
+    fn rand_edge_size(&self, env: &SimEnv, conf: &DagGenConfig, from: NodeIndex) -> f32 {
+        match conf.edge_size {
+            Some((min, max)) => env.env_rand_f(min, max),
+            None => env.func(self.dag_inner[from]).out_put_size,
+        }
+    }
+
+    // 分层随机DAG：第一层为起始函数，之后每层的函数至少连接上一层的一个函数，
+    // 并以 density 的概率与更前面各层的函数连边
+    pub fn instance_layered(dag_i: DagId, env: &SimEnv, conf: &DagGenConfig) -> FnDAG {
+        let begin_fn = env.fn_gen_dag_fn(None);
+        let mut dag = FnDAG::new(begin_fn, dag_i, env);
+
+        let depth = env.env_rand_i(conf.depth.0, conf.depth.1 + 1);
+        let mut layers: Vec<Vec<NodeIndex>> = vec![vec![dag.begin_fn_g_i]];
+        for _ in 1..depth {
+            let width = env.env_rand_i(conf.width.0, conf.width.1 + 1);
+            let mut layer = vec![];
+            for _ in 0..width {
+                let g_i = dag.add_rand_fn(env);
+
+                // 至少有一个上一层的前驱，保证从起始函数可达
+                let prev_layer = layers.last().unwrap();
+                let parent = prev_layer[env.env_rand_i(0, prev_layer.len())];
+                let size = dag.rand_edge_size(env, conf, parent);
+                dag.dag_inner.add_edge(parent, g_i, size).unwrap();
+                let mut fan_in = 1;
+
+                for &p in layers.iter().flatten() {
+                    if conf.max_fan_in.map_or(false, |max| fan_in >= max) {
+                        break;
+                    }
+                    if p == parent || env.env_rand_f(0.0, 1.0) >= conf.density {
+                        continue;
+                    }
+                    let size = dag.rand_edge_size(env, conf, p);
+                    dag.dag_inner.add_edge(p, g_i, size).unwrap();
+                    fan_in += 1;
+                }
+                layer.push(g_i);
+            }
+            if !layer.is_empty() {
+                layers.push(layer);
+            }
+        }
+
+        dag
+    }
+
+    // 随机串并联DAG：从 起始函数->结束函数 开始，每次随机选一条边 u->v 加入新函数 x，
+    // 并联时保留 u->v 并加入 u->x->v，串联时将 u->v 替换为 u->x->v
+    pub fn instance_series_parallel(dag_i: DagId, env: &SimEnv, conf: &DagGenConfig) -> FnDAG {
+        let begin_fn = env.fn_gen_dag_fn(None);
+        let mut dag = FnDAG::new(begin_fn, dag_i, env);
+
+        let fn_cnt = env.env_rand_i(conf.sp_fn_cnt.0, conf.sp_fn_cnt.1 + 1).max(2);
+        let end_g_i = dag.add_rand_fn(env);
+        let size = dag.rand_edge_size(env, conf, dag.begin_fn_g_i);
+        dag.dag_inner.add_edge(dag.begin_fn_g_i, end_g_i, size).unwrap();
+
+        for _ in 2..fn_cnt {
+            let edge = env.env_rand_i(0, dag.dag_inner.edge_count());
+            let edge = daggy::EdgeIndex::new(edge);
+            let (u, v) = dag.dag_inner.edge_endpoints(edge).unwrap();
+            let parallel = env.env_rand_f(0.0, 1.0) < conf.sp_parallel_prob;
+            let x = dag.add_rand_fn(env);
+            if !parallel {
+                dag.dag_inner.remove_edge(edge);
+            }
+            let size = dag.rand_edge_size(env, conf, u);
+            dag.dag_inner.add_edge(u, x, size).unwrap();
+            let size = dag.rand_edge_size(env, conf, x);
+            dag.dag_inner.add_edge(x, v, size).unwrap();
+        }
+
+        dag
+    }
+
+    // 按应用描述创建DAG实例，第一个没有前驱的函数作为起始函数，
+    // fns 为应用中的函数描述，fnids 为对应的函数
+    pub fn instance_from_spec(
+        dag_i: DagId,
+        env: &SimEnv,
+        app: &AppSpecApp,
+        fns: &[&FnSpec],
+        fnids: &[FnId],
+    ) -> FnDAG {
+        assert!(!fns.is_empty(), "app {} has no fn", app.name);
+        let begin_idx = (0..fns.len())
+            .find(|&i| !app.edges.iter().any(|e| app.fn_idx(fns, &e.to) == i))
+            .unwrap_or_else(|| panic!("app {} has no begin fn", app.name));
+
+        let mut dag = FnDAG::new(fnids[begin_idx], dag_i, env);
+        let mut graph_is = vec![dag.begin_fn_g_i; fnids.len()];
+        for (i, &fnid) in fnids.iter().enumerate() {
+            if i == begin_idx {
+                continue;
+            }
+            graph_is[i] = dag.dag_inner.add_node(fnid);
+            dag.setup_fn_after_insert(env, fnid, graph_is[i]);
+        }
+        for e in &app.edges {
+            let (from, to) = (app.fn_idx(fns, &e.from), app.fn_idx(fns, &e.to));
+            let data_size = e.data_size.unwrap_or(fns[from].out_put_size);
+            let edge = dag
+                .dag_inner
+                .add_edge(graph_is[from], graph_is[to], data_size)
+                .unwrap_or_else(|_| {
+                    panic!("app {} edge {} -> {} makes a cycle", app.name, e.from, e.to)
+                });
+            if let Some(prob) = e.prob {
+                assert!(
+                    (0.0..=1.0).contains(&prob),
+                    "app {} edge {} -> {} prob should be in [0, 1]",
+                    app.name,
+                    e.from,
+                    e.to
+                );
+                dag.edge_probs.insert(edge, prob);
+            }
+        }
+        for (i, f) in fns.iter().enumerate() {
+            if f.choice {
+                dag.choice_fns.insert(graph_is[i]);
+            }
+            if let Some(fan_out) = &f.fan_out {
+                dag.fan_out.insert(graph_is[i], fan_out.clone());
+            }
+        }
+
+        dag
+    }
+
+    // 动态 map DAG：起始函数 -> map 函数 -> 结束函数，map 函数每次请求的并行实例数按 fan_out 抽取
+    pub fn instance_dynamic_map(dag_i: DagId, env: &SimEnv, fan_out: &FanOutConfig) -> FnDAG {
+        let mut dag = FnDAG::instance_chain(dag_i, env, 3);
+        let map_g_i = dag
+            .dag_inner
+            .children(dag.begin_fn_g_i)
+            .iter(&dag.dag_inner)
+            .next()
+            .unwrap()
+            .1;
+        dag.fan_out.insert(map_g_i, fan_out.clone());
+        dag
+    }
+
     // 创建一个复杂DAG实例
     pub fn instance_map_reduce(dag_i: DagId, env: &SimEnv, map_cnt: usize) -> FnDAG {
         // 创建一个Func实例并获得fnid，设置为该DAG的起始函数
-        let begin_fn = env.fn_gen_rand_fn();
+        let begin_fn = env.fn_gen_dag_fn(None);
 
         // 创建一个FnDAG实例，并设置起始函数
         let mut dag = FnDAG::new(begin_fn, dag_i, env);
 
         // 创建一个Func实例并获得fnid，设置为该DAG的结束函数
-        let end_fn = env.fn_gen_rand_fn();
+        let end_fn = env.fn_gen_dag_fn(Some(&dag));
         // 为DAG添加一个结束函数，并返回它的节点索引
         let end_g_i = dag.dag_inner.add_node(end_fn);
 
         // 设置这个函数实例的DAGid以及在这个DAG中的位置
-        env.func_mut(end_fn)
-            .setup_after_insert_into_dag(dag_i, end_g_i);
+        dag.setup_fn_after_insert(env, end_fn, end_g_i);
 
         // 往DAG图里插入 map_cnt 数量的节点
         for _i in 0..map_cnt {
             // 创建Func实例并获得fnid
-            let next = env.fn_gen_rand_fn();
+            let next = env.fn_gen_dag_fn(Some(&dag));
 
             // 为DAG图插入一个节点，是初始节点的子节点，并返回它的节点索引
             let (_, next_i) = dag.dag_inner.add_child(
@@ -84,8 +385,7 @@ impl FnDAG {
                 next,
             );
             // 设置这个函数实例的DAGid以及在这个DAG中的位置
-            env.func_mut(next)
-                .setup_after_insert_into_dag(dag_i, next_i);
+            dag.setup_fn_after_insert(env, next, next_i);
 
             // 为DAG添加边,让中间节点连接到结束节点
             dag.dag_inner
@@ -104,18 +404,21 @@ impl FnDAG {
         Topo::new(&self.dag_inner)
     }
 
-    pub fn contains_fn(&self, env: &SimEnv, fnid: FnId) -> bool {
-        let gi = env.func(fnid).graph_i;
-        self.dag_inner.node_weight(gi).is_some() && self.dag_inner[gi] == fnid
+    // 默认 SLO，关键路径上每个函数 SLO_FRAMES_PER_FN 帧
+    pub fn default_slo(&self) -> usize {
+        util::graph::critical_path(&self.dag_inner).len() * SLO_FRAMES_PER_FN
+    }
+
+    pub fn contains_fn(&self, fnid: FnId) -> bool {
+        self.fn_graph_i.contains_key(&fnid)
     }
 }
 
 pub struct Func {
     pub fn_id: FnId,
 
-    pub dag_id: DagId,
-
-    pub graph_i: NodeIndex,
+    // 引用这个函数的DAG
+    pub dags: BTreeSet<DagId>,
 
     // #  #运算量/s 一个普通请求处理函数请求的运算量为1，
     pub cpu: f32, // 1
@@ -132,24 +435,38 @@ pub struct Func {
     // frame count of cold start
     pub cold_start_time: usize,
 
+    // 冷启动各阶段中最大的内存占用，放置容器时按它判断内存是否足够
     pub cold_start_container_mem_use: f32,
 
+    // 冷启动各阶段中最大的cpu占用
     pub cold_start_container_cpu_use: f32,
-}
 
-impl Func {
-    pub fn parent_fns(&self, env: &SimEnv) -> Vec<FnId> {
-        let dag = env.dag_inner(self.dag_id);
-        let ps = dag.parents(self.graph_i);
-        ps.iter(&dag).map(|(_edge, graph_i)| dag[graph_i]).collect()
-    }
+    // 冷启动依次经过的阶段
+    pub cold_start_phases: Vec<ColdStartPhase>,
 
-    // 设置这个函数实例的DAGid以及在这个DAG中的位置
-    pub fn setup_after_insert_into_dag(&mut self, dag_i: DagId, graph_i: NodeIndex) {
-        self.dag_id = dag_i;
-        self.graph_i = graph_i;
-    }
+    // 函数原型，None 表示按 cpu/data 类型生成或由应用描述文件指定
+    pub archetype: Option<FnArchetype>,
+
+    // 每次执行的运算量相对 cpu 的倍数分布，None 表示每次执行的运算量都为 cpu
+    pub cpu_distr: Option<VarDistr>,
+
+    // 每次执行的输出数据量相对 out_put_size 的倍数分布
+    pub out_distr: Option<VarDistr>,
+
+    // 运算量和输出数据量与请求输入规模的相关程度 (0~1)
+    pub input_corr: f32,
+
+    // 每个容器同时执行的任务数，None 表示不限制
+    pub concurrency: Option<usize>,
 
+    // 函数的运行时，None 表示不能使用预热通用容器
+    pub runtime: Option<String>,
+
+    // 函数的镜像，None 表示不模拟镜像拉取
+    pub image: Option<FnImage>,
+}
+
+impl Func {
     // 容器启动时,分配的内存
     pub fn container_mem(&self) -> f32 {
         CONTAINER_BASIC_MEM
@@ -167,8 +484,18 @@ pub enum FnContainerState {
 pub struct FnContainer {
     pub node_id: NodeId,
     pub fn_id: FnId,
+    // 同一节点上同一函数的副本编号
+    pub replica_id: ReplicaId,
+    // 该容器冷启动依次经过的阶段，通常为函数的冷启动阶段
+    cold_start_phases: Vec<ColdStartPhase>,
     pub req_fn_state: HashMap<ReqId, RunningTask>,
+    // 函数限制了并发数时，在容器前排队等待执行的任务和入队的帧
+    queue: VecDeque<(ReqId, usize)>,
+    // queue 中的任务，用于快速判断任务是否在排队
+    queued: HashSet<ReqId>,
     pub born_frame: usize,
+    // 容器最近一次处于启动中、有任务或被使用的帧，用于计算空闲时长
+    pub last_busy_frame: usize,
     pub used_times: usize,
     pub this_frame_used: bool,
     pub recent_frames_done_cnt: VecDeque<usize>,
@@ -190,8 +517,10 @@ const WORKING_CNT_WINDOW: usize = 20;
 
 impl FnContainer {
     pub fn mem_take(&self, env: &SimEnv) -> f32 {
-        match self.state() {
-            FnContainerState::Starting { .. } => env.func(self.fn_id).cold_start_container_mem_use,
+        match *self.state() {
+            FnContainerState::Starting { left_frame } => {
+                self.cold_start_phase(left_frame).mem
+            }
             FnContainerState::Running => env.func(self.fn_id).container_mem(),
         }
     }
@@ -253,19 +582,36 @@ impl FnContainer {
         }
     }
 
-    pub fn new(fn_id: FnId, node_id: NodeId, sim_env: &SimEnv) -> Self {
+    // cold_start_phases 为空时容器直接进入运行状态
+    pub fn new(
+        fn_id: FnId,
+        replica_id: ReplicaId,
+        node_id: NodeId,
+        cold_start_phases: Vec<ColdStartPhase>,
+        sim_env: &SimEnv,
+    ) -> Self {
+        let cold_start_time: usize = cold_start_phases.iter().map(|p| p.frames).sum();
         Self {
             node_id,
             fn_id,
+            replica_id,
+            cold_start_phases,
             req_fn_state: HashMap::default(),
+            queue: VecDeque::new(),
+            queued: HashSet::new(),
             born_frame: sim_env.current_frame(),
+            last_busy_frame: sim_env.current_frame(),
             used_times: 0,
             this_frame_used: false,
             cpu_use_rate: 0.0,
             mem_use: CONTAINER_BASIC_MEM,
             last_frame_mem: 0.0,
-            state: FnContainerState::Starting {
-                left_frame: sim_env.func(fn_id).cold_start_time,
+            state: if cold_start_time == 0 {
+                FnContainerState::Running
+            } else {
+                FnContainerState::Starting {
+                    left_frame: cold_start_time,
+                }
             },
             recent_frames_done_cnt: VecDeque::new(),
             recent_frames_working_cnt: VecDeque::new(),
@@ -287,13 +633,36 @@ impl FnContainer {
         }
     }
 
-    pub fn container_basic_mem(&self, env: &SimEnv) -> f32 {
+    // 冷启动还剩 left_frame 帧时所处的阶段
+    pub fn cold_start_phase(&self, left_frame: usize) -> &ColdStartPhase {
+        let cold_start_time: usize = self.cold_start_phases.iter().map(|p| p.frames).sum();
+        let mut passed = cold_start_time - left_frame;
+        for phase in &self.cold_start_phases {
+            if passed < phase.frames {
+                return phase;
+            }
+            passed -= phase.frames;
+        }
+        panic!("fn {} container cold start already finished", self.fn_id);
+    }
+
+    pub fn container_basic_mem(&self) -> f32 {
         match self.state {
-            FnContainerState::Starting { .. } => env.func(self.fn_id).cold_start_container_mem_use,
+            FnContainerState::Starting { left_frame } => self.cold_start_phase(left_frame).mem,
             FnContainerState::Running => CONTAINER_BASIC_MEM,
         }
     }
 
+    // 冷启动再推进一帧后容器的内存占用
+    pub fn starting_next_frame_mem(&self, env: &SimEnv) -> f32 {
+        match self.state {
+            FnContainerState::Starting { left_frame } if left_frame > 1 => {
+                self.cold_start_phase(left_frame - 1).mem
+            }
+            _ => env.func(self.fn_id).container_mem(),
+        }
+    }
+
     // pub fn calc_mem_used(&self, env: &SimEnv) -> f32 {
     //     match self.state {
     //         FnContainerState::Starting { .. } => env.func(self.fn_id).cold_start_container_mem_use,
@@ -336,6 +705,70 @@ impl FnContainer {
             FnContainerState::Starting { .. } => false,
         }
     }
+
+    // 容器已经空闲的帧数，不空闲时返回 None
+    pub fn idle_frames(&self, env: &SimEnv) -> Option<usize> {
+        if !self.is_idle() {
+            return None;
+        }
+        Some(env.current_frame() - self.last_busy_frame)
+    }
+
+    // 将任务交给容器，函数限制了并发数时先进入队列，由 dispatch_queued 按 FIFO 放行
+    pub fn add_task(&mut self, req_id: ReqId, task: RunningTask, env: &SimEnv) {
+        self.req_fn_state.insert(req_id, task);
+        if env.func(self.fn_id).concurrency.is_some() {
+            self.queue.push_back((req_id, env.current_frame()));
+            self.queued.insert(req_id);
+        }
+    }
+
+    pub fn remove_task(&mut self, req_id: ReqId) -> Option<RunningTask> {
+        if self.queued.remove(&req_id) {
+            self.queue.retain(|&(r, _)| r != req_id);
+        }
+        self.req_fn_state.remove(&req_id)
+    }
+
+    // 任务还在排队，不接收数据也不参与计算
+    pub fn is_queued(&self, req_id: ReqId) -> bool {
+        self.queued.contains(&req_id)
+    }
+
+    pub fn queued_reqs(&self) -> Vec<ReqId> {
+        self.queue.iter().map(|&(r, _)| r).collect()
+    }
+
+    // 正在执行的实例数，动态 map 函数的每个实例占用一个并发
+    fn executing_instances(&self) -> usize {
+        self.req_fn_state
+            .iter()
+            .filter(|(r, _)| !self.is_queued(**r))
+            .map(|(_, t)| t.instances)
+            .sum()
+    }
+
+    // 有空闲并发时按 FIFO 放行排队的任务，并记录请求在该函数上的排队帧数
+    // 实例数超过并发数的任务在容器空闲时独占放行，避免永远无法执行
+    pub fn dispatch_queued(&mut self, env: &SimEnv) {
+        let Some(concurrency) = env.func(self.fn_id).concurrency else {
+            return;
+        };
+        let mut executing = self.executing_instances();
+        while let Some(&(req_id, enqueue_frame)) = self.queue.front() {
+            let instances = self.req_fn_state[&req_id].instances;
+            if executing > 0 && executing + instances > concurrency {
+                break;
+            }
+            self.queue.pop_front();
+            self.queued.remove(&req_id);
+            executing += instances;
+            let wait = env.current_frame() - enqueue_frame;
+            let mut req = env.request_mut(req_id);
+            let queue_frames = req.fn_queue_frames.entry(self.fn_id).or_insert(0);
+            *queue_frames = (*queue_frames).max(wait);
+        }
+    }
 }
 
 pub struct RunningTask {
@@ -344,6 +777,9 @@ pub struct RunningTask {
 
     /// 剩余计算量
     pub left_calc: f32,
+
+    /// 本次请求在该容器中并行执行的实例数，动态 map 函数可能大于 1
+    pub instances: usize,
 }
 
 impl RunningTask {
@@ -364,62 +800,243 @@ impl RunningTask {
 }
 
 impl SimEnv {
+    // 为DAG选择一个函数：以 shared_fn_prob 的概率复用共享函数池中DAG里还没有的函数，否则新建一个函数
+    fn fn_gen_dag_fn(&self, dag: Option<&FnDAG>) -> FnId {
+        let shared_fns = self.core.shared_fns();
+        if !shared_fns.is_empty()
+            && self.env_rand_f(0.0, 1.0) < self.help.config().dag_gen_conf().shared_fn_prob
+        {
+            let candidates: Vec<FnId> = shared_fns
+                .iter()
+                .copied()
+                .filter(|&f| !dag.is_some_and(|dag| dag.contains_fn(f)))
+                .collect();
+            if !candidates.is_empty() {
+                return candidates[self.env_rand_i(0, candidates.len())];
+            }
+        }
+        drop(shared_fns);
+        self.fn_gen_rand_fn()
+    }
+
+    // 生成共享函数池，池中的函数可以被多个应用引用
+    fn fn_gen_shared_fns(&self, cnt: usize) {
+        for _ in 0..cnt {
+            let fnid = self.fn_gen_rand_fn();
+            self.core.shared_fns_mut().push(fnid);
+        }
+    }
+
     // 创建一个Func实例
     fn fn_gen_rand_fn(&self) -> FnId {
         // 获得fnid
         let id = self.help.fn_next_id();
-        // 根据不同的函数类型确定cpu和输出数据量
-        let (cpu, out_put_size) = 
-        if self.help.config().fntype_cpu() {
-            (self.env_rand_f(10.0, 100.0), self.env_rand_f(0.1, 20.0))
-        } 
-        else if self.help.config().fntype_data() {
-            (self.env_rand_f(10.0, 100.0), self.env_rand_f(30.0, 100.0))
-        } 
-        else {
-            panic!("not support fntype");
+        // 根据不同的函数类型确定cpu、输出数据量和内存
+        let archetype = self.fn_gen_archetype();
+        let (cpu, out_put_size, mem) = if let Some(archetype) = archetype {
+            self.fn_gen_archetype_rsc(archetype)
+        } else {
+            let (cpu, out_put_size) =
+            if self.help.config().fntype_cpu() {
+                (self.env_rand_f(10.0, 100.0), self.env_rand_f(0.1, 20.0))
+            }
+            else if self.help.config().fntype_data() {
+                (self.env_rand_f(10.0, 100.0), self.env_rand_f(30.0, 100.0))
+            }
+            else {
+                panic!("not support fntype");
+            };
+            (cpu, out_put_size, self.env_rand_f(100.0, 1000.0))
         };
+        let exec_var = &self.help.config().exec_var;
+        let cold_start_phases = self.fn_gen_cold_start_phases(archetype);
+        let (cold_start_time, cold_start_container_mem_use, cold_start_container_cpu_use) =
+            cold_start::phases_summary(&cold_start_phases);
 
         // 创建一个Func实例并加入到core中
         self.core.fns_mut().push(Func {
             fn_id: id,
             cpu,
-            mem: self.env_rand_f(100.0, 1000.0),
+            mem,
             out_put_size,
             nodes: HashSet::new(),
-            cold_start_container_mem_use: self.env_rand_f(100.0, 500.0),
-            cold_start_container_cpu_use: self.env_rand_f(0.1, 50.0),
-            cold_start_time: self.env_rand_i(5, 10),
-            dag_id: 0,
-            graph_i: (0).into(),
+            cold_start_container_mem_use,
+            cold_start_container_cpu_use,
+            cold_start_time,
+            cold_start_phases,
+            archetype,
+            dags: BTreeSet::new(),
+            cpu_distr: exec_var.as_ref().and_then(|e| e.cpu.clone()),
+            out_distr: exec_var.as_ref().and_then(|e| e.output.clone()),
+            input_corr: exec_var.as_ref().map_or(0.0, |e| e.input_corr),
+            concurrency: self.help.config().container_concurrency,
+            runtime: self.fn_gen_runtime(),
+            image: self.fn_gen_image(None, None),
         });
         id
     }
 
-    fn gen_dags_for_apptype(&self, apptype: &APPConfig, env: &SimEnv) {
-        for _ in 0..apptype.app_cnt {
-            match &*apptype.dag_type {
-                "single" => {
-                    let dag_i = self.core.dags().len();
-                    // (appconfig: &APPConfig, dag_i: DagId, env: &SimEnv)
-                    let dag = FnDAG::instance_single_fn(dag_i, env);
-                    self.core.dags_mut().push(dag);
-
-                    // let mapcnt = env.env_rand_i(2, 5); //2-4
-                    //         let dag_i = env.dags.borrow().len();
-                    //         let dag = FnDAG::instance_map_reduce(dag_i, env, mapcnt);
-                }
-                "chain" => {}
-                "branch" => {
-                    let mapcnt = self.env_rand_i(2, 5); //2-4
-                    let dag_i = self.core.dags().len();
-                    let dag = FnDAG::instance_map_reduce(dag_i, env, mapcnt);
-                    self.core.dags_mut().push(dag);
-                }
-                _ => {
-                    panic!("not support")
-                }
+    // 按函数描述创建函数，未设置冷启动参数时按 cold_start 配置生成冷启动阶段，
+    // 只设置了 cold_start_time 等整体参数时整个冷启动作为一个阶段，未设置的参数随机生成
+    fn fn_gen_spec_fn(&self, spec: &FnSpec) -> FnId {
+        let id = self.help.fn_next_id();
+        let cold_start_phases = if let Some(phases) = &spec.cold_start_phases {
+            phases.clone()
+        } else if spec.cold_start_time.is_some()
+            || spec.cold_start_container_mem_use.is_some()
+            || spec.cold_start_container_cpu_use.is_some()
+        {
+            vec![ColdStartPhase {
+                phase: ColdStartPhaseKind::Sandbox,
+                mem: spec
+                    .cold_start_container_mem_use
+                    .unwrap_or_else(|| self.env_rand_f(100.0, 500.0)),
+                cpu: spec
+                    .cold_start_container_cpu_use
+                    .unwrap_or_else(|| self.env_rand_f(0.1, 50.0)),
+                frames: spec
+                    .cold_start_time
+                    .unwrap_or_else(|| self.env_rand_i(5, 10)),
+            }]
+        } else {
+            self.fn_gen_cold_start_phases(None)
+        };
+        assert!(
+            cold_start_phases.iter().map(|p| p.frames).sum::<usize>() > 0,
+            "fn {} cold start should take at least one frame",
+            spec.name
+        );
+        let (cold_start_time, cold_start_container_mem_use, cold_start_container_cpu_use) =
+            cold_start::phases_summary(&cold_start_phases);
+        let exec_var = &self.help.config().exec_var;
+        self.core.fns_mut().push(Func {
+            fn_id: id,
+            cpu: spec.cpu,
+            mem: spec.mem,
+            out_put_size: spec.out_put_size,
+            nodes: HashSet::new(),
+            cold_start_container_mem_use,
+            cold_start_container_cpu_use,
+            cold_start_time,
+            cold_start_phases,
+            archetype: None,
+            dags: BTreeSet::new(),
+            cpu_distr: spec
+                .cpu_distr
+                .clone()
+                .or_else(|| exec_var.as_ref().and_then(|e| e.cpu.clone())),
+            out_distr: spec
+                .out_distr
+                .clone()
+                .or_else(|| exec_var.as_ref().and_then(|e| e.output.clone())),
+            input_corr: spec
+                .input_corr
+                .unwrap_or_else(|| exec_var.as_ref().map_or(0.0, |e| e.input_corr)),
+            concurrency: spec
+                .concurrency
+                .or(self.help.config().container_concurrency),
+            runtime: spec.runtime.clone().or_else(|| self.fn_gen_runtime()),
+            image: self.fn_gen_image(spec.base_layer, spec.image_size),
+        });
+        id
+    }
+
+    // 按应用描述文件生成应用
+    fn gen_dags_from_spec(&self, spec: &AppSpec) {
+        // 共享函数只创建一次，被引用它的应用共用
+        let shared: HashMap<&str, FnId> = spec
+            .shared_fns
+            .iter()
+            .map(|f| (&*f.name, self.fn_gen_spec_fn(f)))
+            .collect();
+        self.core
+            .shared_fns_mut()
+            .extend(spec.shared_fns.iter().map(|f| shared[&*f.name]));
+
+        for app in &spec.apps {
+            let dag_i = self.core.dags().len();
+            let fns = spec.app_fns(app);
+            let fnids: Vec<FnId> = fns
+                .iter()
+                .enumerate()
+                .map(|(i, f)| {
+                    if i < app.fns.len() {
+                        self.fn_gen_spec_fn(f)
+                    } else {
+                        shared[&*f.name]
+                    }
+                })
+                .collect();
+            let mut dag = FnDAG::instance_from_spec(dag_i, self, app, &fns, &fnids);
+            dag.weight = app.weight.unwrap_or(1.0);
+            dag.req_rate = app.rate;
+            dag.slo = app.slo.unwrap_or_else(|| dag.default_slo());
+            log::info!("dag {} {} {:?}", dag.dag_i, app.name, dag.dag_inner);
+            self.core.dags_mut().push(dag);
+        }
+    }
+
+    // 生成一个指定类型的应用：single, chain, dag(branch), layered, series_parallel, dynamic_map, mix
+    fn gen_dag_of_type(&self, dag_type: &str) -> FnDAG {
+        let env = self;
+        let dag_gen = self.help.config().dag_gen_conf();
+        let dag_i = self.core.dags().len();
+        let mut dag = match dag_type {
+            "single" => FnDAG::instance_single_fn(dag_i, env),
+            "chain" => {
+                let (min, max) = dag_gen.chain_len;
+                assert!(min >= 1 && min <= max, "invalid chain_len ({}, {})", min, max);
+                let len = self.env_rand_i(min, max + 1);
+                FnDAG::instance_chain(dag_i, env, len)
             }
+            "branch" | "dag" => {
+                let mapcnt = self.env_rand_i(2, 5); //2-4
+                FnDAG::instance_map_reduce(dag_i, env, mapcnt)
+            }
+            "layered" => FnDAG::instance_layered(dag_i, env, &dag_gen),
+            "series_parallel" => FnDAG::instance_series_parallel(dag_i, env, &dag_gen),
+            "dynamic_map" => FnDAG::instance_dynamic_map(dag_i, env, &dag_gen.fan_out),
+            "mix" => {
+                // 按权重随机选择一种应用
+                let (single, chain, dag) = dag_gen.mix_weights;
+                let r = self.env_rand_f(0.0, single + chain + dag);
+                return if r < single {
+                    self.gen_dag_of_type("single")
+                } else if r < single + chain {
+                    self.gen_dag_of_type("chain")
+                } else {
+                    self.gen_dag_of_type("dag")
+                };
+            }
+            _ => {
+                panic!("not support dag type {}", dag_type)
+            }
+        };
+        dag.rand_choice_fns(env, dag_gen.choice_prob);
+        dag
+    }
+
+    fn gen_dags_for_apptype(&self, apptype: &APPConfig) {
+        let first_dag_i = self.core.dags().len();
+        for _ in 0..apptype.app_cnt {
+            let dag = self.gen_dag_of_type(&apptype.dag_type);
+            self.core.dags_mut().push(dag);
+        }
+
+        // 设置这类应用的权重和请求速率，第 k 个应用的 Zipf 权重为 1/k^s
+        let mut dags = self.core.dags_mut();
+        let app_dags = &mut dags[first_dag_i..];
+        let ranks: Vec<f32> = (1..=app_dags.len())
+            .map(|k| apptype.zipf.map_or(1.0, |s| 1.0 / (k as f32).powf(s)))
+            .collect();
+        let rank_sum: f32 = ranks.iter().sum();
+        let app_cnt = app_dags.len() as f32;
+        for (dag, rank) in app_dags.iter_mut().zip(ranks) {
+            // 这个应用相对于这类应用平均水平的倍数
+            let share = rank / rank_sum * app_cnt;
+            dag.weight = apptype.weight.map_or(share, |w| w * share / app_cnt);
+            dag.req_rate = apptype.rate.map(|r| r * share);
+            dag.slo = apptype.slo.unwrap_or_else(|| dag.default_slo());
         }
     }
 
@@ -431,38 +1048,51 @@ impl SimEnv {
         //     let dag = FnDAG::instance_map_reduce(dag_i, env, util::rand_i(2, 10));
         //     env.dags.borrow_mut().push(dag);
         // }
-        // for app in &self.config.app_types {
-        //     // (&self, apptype: &APPConfig, env: &SimEnv)
-        //     self.gen_dags_for_apptype(app, env);
-        // }
-
-        // 检查配置中的dag_type
-        if self.help.config().dag_type_dag() {
-            // 如果dag_type为dag，则创建6个具有多个子节点的复杂DAG实例
-            for _ in 0..6 {
-                // 随机确定每个图中节点的数量
-                let mapcnt = env.env_rand_i(2, 5); //2-4
-                let dag_i = env.core.dags().len();
-
-                // 创建一个复杂DAG实例
-                let dag = FnDAG::instance_map_reduce(dag_i, env, mapcnt);
-                log::info!("dag {} {:?}", dag.dag_i, dag.dag_inner);
+        // 随机生成应用时先生成共享函数池，应用描述文件中的共享函数在文件中声明
+        if self.help.config().app_spec.is_none() {
+            self.fn_gen_shared_fns(self.help.config().dag_gen_conf().shared_fn_cnt);
+        }
 
-                env.core.dags_mut().push(dag);
+        // 配置了应用描述文件时按文件生成
+        if let Some(path) = &self.help.config().app_spec {
+            self.gen_dags_from_spec(&AppSpec::load(path));
+        }
+        // 配置了应用类型时按应用类型生成
+        else if !self.help.config().app_types.is_empty() {
+            for app in &self.help.config().app_types {
+                self.gen_dags_for_apptype(app);
             }
-        } 
-        // 如果dag_type为single，则创建10个只包含单个节点的简单DAG实例
-        else if self.help.config().dag_type_single() {
-            for _ in 0..10 {
-                let dag_i = env.core.dags().len();
-
-                // 创建一个简单DAG实例
-                let dag = FnDAG::instance_single_fn(dag_i, env);
+            assert!(!env.core.dags().is_empty(), "app_types generate no app");
+        }
+        // 按配置中的 dag_type 生成，single 和 mix 默认 10 个应用，其它类型默认 6 个
+        else {
+            let config = self.help.config();
+            let default_app_cnt = if config.dag_type_single() || config.dag_type_mix() {
+                10
+            } else if config.dag_type_dag()
+                || config.dag_type_chain()
+                || config.dag_type_layered()
+                || config.dag_type_series_parallel()
+                || config.dag_type_dynamic_map()
+            {
+                6
+            } else {
+                panic!("not support dag type {}", config.dag_type);
+            };
+            for _ in 0..config.dag_gen_conf().app_cnt.unwrap_or(default_app_cnt) {
+                let mut dag = self.gen_dag_of_type(&config.dag_type);
+                dag.slo = dag.default_slo();
+                log::info!("dag {} {:?}", dag.dag_i, dag.dag_inner);
                 env.core.dags_mut().push(dag);
             }
-        } 
-        else {
-            panic!("not support dag type {}", self.help.config().dag_type);
+        }
+        self.log_shared_fns();
+    }
+
+    // 输出被多个应用引用的函数
+    fn log_shared_fns(&self) {
+        for func in self.core.fns().iter().filter(|f| f.dags.len() > 1) {
+            log::info!("fn {} shared by dags {:?}", func.fn_id, func.dags);
         }
     }
 
@@ -472,24 +1102,46 @@ impl SimEnv {
     //     dag.dag[dag.begin_fn_g_i] == fn_i
     // }
 
-    pub fn fn_new_fn_running_state(&self, req: &Request, fnid: FnId) -> RunningTask {
+    pub fn fn_new_fn_running_state(&self, req: &Request, fnid: FnId, nodeid: NodeId) -> RunningTask {
         let env = self;
 
-        let total_calc: f32 = env.func(fnid).cpu;
-        let fngi = env.func(fnid).graph_i;
+        let total_calc: f32 = env.func(fnid).cpu * env.fn_exec_factors(req, fnid).0;
         let mut need_node_data: HashMap<NodeId, f32> = HashMap::new();
         let dag_i = req.dag_i;
         let env_dags = env.core.dags();
         let dag = &env_dags[dag_i];
-        for (_, pgi) in dag.dag_inner.parents(fngi).iter(&dag.dag_inner) {
+        let fngi = dag.graph_i(fnid);
+        let fired_parents = req.parent_fns(env, fnid);
+        let instances = req.fn_instances_on(fnid, nodeid);
+        for (e, pgi) in dag.dag_inner.parents(fngi).iter(&dag.dag_inner) {
             let p: FnId = dag.dag_inner[pgi];
-            let node = req.get_fn_node(p).unwrap();
-            need_node_data
-                .entry(node)
-                .and_modify(|v| {
-                    *v += env.core.fns()[p].out_put_size;
-                })
-                .or_insert(env.core.fns()[p].out_put_size);
+            // 条件DAG中未触发的边没有数据传输
+            if !fired_parents.contains(&p) {
+                continue;
+            }
+            let p_out_factor = env.fn_exec_factors(req, p).1;
+            let p_nodes = req.fn_nodes(p);
+            assert!(
+                !p_nodes.is_empty(),
+                "req {} fn {} parent {} not scheduled",
+                req.req_id,
+                fnid,
+                p
+            );
+            for (node, p_instances) in p_nodes {
+                // 边上的权重为前驱函数传给该函数的数据量，按前驱本次执行的输出数据量倍数缩放，
+                // 动态 map 函数的数据在各实例间均分
+                let data = dag.dag_inner[e]
+                    * p_out_factor
+                    * (p_instances as f32 / req.fn_width(p) as f32)
+                    * (instances as f32 / req.fn_width(fnid) as f32);
+                need_node_data
+                    .entry(node)
+                    .and_modify(|v| {
+                        *v += data;
+                    })
+                    .or_insert(data);
+            }
         }
         RunningTask {
             data_recv: need_node_data
@@ -498,9 +1150,26 @@ impl SimEnv {
                 .collect(),
 
             left_calc: total_calc,
+            instances,
         }
     }
 
+    // 函数在请求中本次执行的运算量倍数和输出数据量倍数，只由随机种子、请求和函数决定
+    pub fn fn_exec_factors(&self, req: &Request, fnid: FnId) -> (f32, f32) {
+        let func = self.func(fnid);
+        if func.cpu_distr.is_none() && func.out_distr.is_none() && func.input_corr == 0.0 {
+            return (1.0, 1.0);
+        }
+        let mut rng = self.req_fn_rander(req.req_id, fnid);
+        let mut factor = |distr: &Option<VarDistr>| {
+            let own = distr.as_ref().map_or(1.0, |d| d.sample(&mut rng));
+            func.input_corr * req.input_size + (1.0 - func.input_corr) * own
+        };
+        let cpu = factor(&func.cpu_distr);
+        let out = factor(&func.out_distr);
+        (cpu, out)
+    }
+
     pub fn func<'a>(&'a self, i: FnId) -> Ref<'a, Func> {
         let b = self.core.fns();
 
@@ -525,17 +1194,21 @@ impl SimEnv {
         Ref::map(b, |vec| &vec[i])
     }
 
+    // 函数在所有节点上的容器副本总数
     pub fn fn_container_cnt(&self, fnid: FnId) -> usize {
         let map = self.core.fn_2_nodes();
-        map.get(&fnid).map_or_else(|| 0, |nodes| nodes.len())
+        map.get(&fnid)
+            .map_or_else(|| 0, |nodes| nodes.values().map(|r| r.len()).sum())
     }
 
     pub fn fn_containers_for_each<F: FnMut(&FnContainer)>(&self, fnid: FnId, mut f: F) {
         let map = self.core.fn_2_nodes();
         if let Some(nodes) = map.get(&fnid) {
-            for node in nodes.iter() {
+            for (node, replicas) in nodes.iter() {
                 let node = self.node(*node);
-                f(&node.container(fnid).unwrap());
+                for &r in replicas {
+                    f(&node.container(fnid, r).unwrap());
+                }
             }
         }
     }
@@ -0,0 +1,140 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{cold_start::ColdStartPhaseKind, config::ColdStartPhaseConfig, sim_env::SimEnv};
+
+/// 函数原型，每种原型有各自的资源需求分布和冷启动特点
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum FnArchetype {
+    /// 计算密集
+    CpuBound,
+    /// I/O 密集，运算量小
+    IoBound,
+    /// 内存占用大
+    MemoryHeavy,
+    /// 输出数据量大
+    DataProducing,
+    /// 模型推理，运算量和内存都大，冷启动需要拉取大镜像并加载模型
+    MlInference,
+}
+
+pub const ALL_ARCHETYPES: [FnArchetype; 5] = [
+    FnArchetype::CpuBound,
+    FnArchetype::IoBound,
+    FnArchetype::MemoryHeavy,
+    FnArchetype::DataProducing,
+    FnArchetype::MlInference,
+];
+
+/// 原型对某个冷启动阶段的缩放倍数
+struct PhaseScale {
+    phase: ColdStartPhaseKind,
+    frames: f32,
+    cpu: f32,
+    mem: f32,
+}
+
+impl FnArchetype {
+    pub fn from_str(s: &str) -> Option<Self> {
+        ALL_ARCHETYPES.iter().copied().find(|a| a.str() == s)
+    }
+
+    pub fn str(&self) -> &'static str {
+        match self {
+            FnArchetype::CpuBound => "cpu_bound",
+            FnArchetype::IoBound => "io_bound",
+            FnArchetype::MemoryHeavy => "memory_heavy",
+            FnArchetype::DataProducing => "data_producing",
+            FnArchetype::MlInference => "ml_inference",
+        }
+    }
+
+    // 运算量、内存 mb、输出数据量 mb 的范围
+    fn rsc_range(&self) -> ((f32, f32), (f32, f32), (f32, f32)) {
+        match self {
+            FnArchetype::CpuBound => ((50.0, 150.0), (100.0, 400.0), (0.1, 5.0)),
+            FnArchetype::IoBound => ((5.0, 20.0), (100.0, 300.0), (1.0, 10.0)),
+            FnArchetype::MemoryHeavy => ((20.0, 60.0), (1000.0, 2500.0), (1.0, 20.0)),
+            FnArchetype::DataProducing => ((10.0, 50.0), (200.0, 800.0), (50.0, 200.0)),
+            FnArchetype::MlInference => ((80.0, 200.0), (800.0, 2000.0), (0.1, 2.0)),
+        }
+    }
+
+    // 在 cold_start 配置的基础上对各冷启动阶段的缩放，未列出的阶段不变
+    fn cold_start_scales(&self) -> Vec<PhaseScale> {
+        let scale = |phase, frames, cpu, mem| PhaseScale {
+            phase,
+            frames,
+            cpu,
+            mem,
+        };
+        match self {
+            FnArchetype::CpuBound | FnArchetype::DataProducing => vec![],
+            FnArchetype::IoBound => vec![scale(ColdStartPhaseKind::UserInit, 1.0, 0.5, 0.5)],
+            FnArchetype::MemoryHeavy => vec![
+                scale(ColdStartPhaseKind::RuntimeInit, 1.0, 1.0, 2.0),
+                scale(ColdStartPhaseKind::UserInit, 1.5, 1.0, 3.0),
+            ],
+            FnArchetype::MlInference => vec![
+                scale(ColdStartPhaseKind::ImagePull, 2.0, 1.0, 1.0),
+                scale(ColdStartPhaseKind::UserInit, 3.0, 2.0, 3.0),
+            ],
+        }
+    }
+
+    /// 按原型调整冷启动各阶段的参数范围
+    pub fn scale_cold_start(&self, profile: &mut [ColdStartPhaseConfig]) {
+        for s in self.cold_start_scales() {
+            for p in profile.iter_mut().filter(|p| p.phase == s.phase) {
+                p.frames = (
+                    (p.frames.0 as f32 * s.frames).round() as usize,
+                    (p.frames.1 as f32 * s.frames).round() as usize,
+                );
+                p.cpu = (p.cpu.0 * s.cpu, p.cpu.1 * s.cpu);
+                p.mem = (p.mem.0 * s.mem, p.mem.1 * s.mem);
+            }
+        }
+    }
+}
+
+impl SimEnv {
+    /// 按 fn_type 为新函数选择原型：cpu、data 不使用原型，
+    /// mix 按 fn_type_weights 的权重（默认等权重）选择，也可以直接指定一种原型
+    pub fn fn_gen_archetype(&self) -> Option<FnArchetype> {
+        let config = self.help.config();
+        match &*config.fn_type {
+            "cpu" | "data" => None,
+            "mix" => {
+                let weights: Vec<(FnArchetype, f32)> = match &config.fn_type_weights {
+                    Some(weights) => weights.iter().map(|(&a, &w)| (a, w)).collect(),
+                    None => ALL_ARCHETYPES.iter().map(|&a| (a, 1.0)).collect(),
+                };
+                let total: f32 = weights.iter().map(|(_, w)| w).sum();
+                assert!(total > 0.0, "fn_type_weights should have positive weight");
+                let mut r = self.env_rand_f(0.0, total);
+                for &(a, w) in &weights {
+                    if r < w {
+                        return Some(a);
+                    }
+                    r -= w;
+                }
+                Some(weights.last().unwrap().0)
+            }
+            t => Some(FnArchetype::from_str(t).unwrap_or_else(|| {
+                panic!(
+                    "not support fntype {}, should be cpu, data, mix or an archetype",
+                    t
+                )
+            })),
+        }
+    }
+
+    /// 按原型生成函数的运算量、输出数据量和内存
+    pub fn fn_gen_archetype_rsc(&self, archetype: FnArchetype) -> (f32, f32, f32) {
+        let (cpu, mem, out) = archetype.rsc_range();
+        let cpu = self.env_rand_f(cpu.0, cpu.1);
+        let out_put_size = self.env_rand_f(out.0, out.1);
+        let mem = self.env_rand_f(mem.0, mem.1);
+        (cpu, out_put_size, mem)
+    }
+}
@@ -0,0 +1,197 @@
+use std::collections::BTreeMap;
+
+use serde::Serialize;
+
+use crate::{
+    cold_start::{ColdStartPhase, ColdStartPhaseKind},
+    fn_dag::FnId,
+    node::Node,
+    sim_env::SimEnv,
+};
+
+/// 镜像层：多个函数共享的基础层，或函数自己的层
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum ImageLayer {
+    Base(usize),
+    Fn(FnId),
+}
+
+/// 函数的镜像，由一个共享基础层和大小为 size mb 的函数层组成
+#[derive(Serialize, Clone, Debug)]
+pub struct FnImage {
+    pub base_layer: usize,
+    pub size: f32,
+}
+
+/// 节点的本地镜像缓存
+pub struct ImageCache {
+    // 镜像层 -> (大小, 最近一次使用的帧)
+    layers: BTreeMap<ImageLayer, (f32, usize)>,
+    // 已使用的磁盘
+    used: f32,
+}
+
+impl ImageCache {
+    pub fn new() -> Self {
+        Self {
+            layers: BTreeMap::new(),
+            used: 0.0,
+        }
+    }
+
+    pub fn contains(&self, layer: ImageLayer) -> bool {
+        self.layers.contains_key(&layer)
+    }
+}
+
+impl Node {
+    /// 在节点上启动函数容器还需要拉取的镜像大小，镜像仓库节点上有所有镜像
+    pub fn image_missing_size(&self, fnid: FnId, env: &SimEnv) -> f32 {
+        let Some(image) = env.help.config().image.as_ref() else {
+            return 0.0;
+        };
+        if self.node_id() == image.registry_node {
+            return 0.0;
+        }
+        let cache = self.images.borrow();
+        env.image_layers(fnid)
+            .into_iter()
+            .filter(|(layer, _)| !cache.contains(*layer))
+            .map(|(_, size)| size)
+            .sum()
+    }
+
+    // 拉取函数镜像缺少的层并更新所有层的使用时间，返回拉取的大小；
+    // 磁盘超出时按 LRU 淘汰没有容器使用的层，正在使用的层不能淘汰，因此磁盘可能暂时超出
+    pub fn image_pull(&self, fnid: FnId, env: &SimEnv) -> f32 {
+        let Some(image) = env.help.config().image.as_ref() else {
+            return 0.0;
+        };
+        if self.node_id() == image.registry_node {
+            return 0.0;
+        }
+        let frame = env.current_frame();
+        let mut pulled = 0.0;
+        let mut cache = self.images.borrow_mut();
+        for (layer, size) in env.image_layers(fnid) {
+            if let Some(l) = cache.layers.get_mut(&layer) {
+                l.1 = frame;
+            } else {
+                cache.layers.insert(layer, (size, frame));
+                cache.used += size;
+                pulled += size;
+            }
+        }
+        if cache.used > image.disk {
+            let mut in_use: Vec<ImageLayer> = vec![];
+            for &(fnid, _) in self.fn_containers.borrow().keys() {
+                in_use.extend(env.image_layers(fnid).into_iter().map(|(layer, _)| layer));
+            }
+            let mut lru: Vec<(usize, ImageLayer)> = cache
+                .layers
+                .iter()
+                .filter(|(layer, _)| !in_use.contains(layer))
+                .map(|(&layer, &(_, last_used))| (last_used, layer))
+                .collect();
+            lru.sort();
+            for (_, layer) in lru {
+                if cache.used <= image.disk {
+                    break;
+                }
+                let (size, _) = cache.layers.remove(&layer).unwrap();
+                cache.used -= size;
+            }
+        }
+        pulled
+    }
+}
+
+impl SimEnv {
+    // 生成共享基础层的大小
+    pub fn image_gen_base_layers(&self) {
+        let Some(image) = self.help.config().image.as_ref() else {
+            return;
+        };
+        assert!(
+            image.registry_node < self.nodes().len(),
+            "image registry node {} should be less than node count {}",
+            image.registry_node,
+            self.nodes().len()
+        );
+        assert!(image.base_layers > 0, "image should have at least one base layer");
+        *self.core.image_base_layers_mut() = (0..image.base_layers)
+            .map(|_| self.env_rand_f_incl(image.base_size.0, image.base_size.1))
+            .collect();
+    }
+
+    // 为新函数生成镜像，没有设置 image 配置时不生成
+    pub fn fn_gen_image(&self, base_layer: Option<usize>, size: Option<f32>) -> Option<FnImage> {
+        let image = self.help.config().image.as_ref()?;
+        let base_layer = base_layer.unwrap_or_else(|| self.env_rand_i(0, image.base_layers));
+        assert!(
+            base_layer < image.base_layers,
+            "fn base layer {} should be less than base layer count {}",
+            base_layer,
+            image.base_layers
+        );
+        Some(FnImage {
+            base_layer,
+            size: size.unwrap_or_else(|| self.env_rand_f_incl(image.fn_size.0, image.fn_size.1)),
+        })
+    }
+
+    // 函数镜像的各层和大小
+    pub fn image_layers(&self, fnid: FnId) -> Vec<(ImageLayer, f32)> {
+        let func = self.func(fnid);
+        let Some(image) = func.image.as_ref() else {
+            return vec![];
+        };
+        vec![
+            (
+                ImageLayer::Base(image.base_layer),
+                self.core.image_base_layers()[image.base_layer],
+            ),
+            (ImageLayer::Fn(fnid), image.size),
+        ]
+    }
+
+    /// 按节点上缺少的镜像重新计算冷启动的镜像拉取阶段：
+    /// 帧数为缺少的大小除以镜像仓库到节点的拉取带宽，镜像已缓存时跳过拉取阶段
+    pub fn image_pull_phases(
+        &self,
+        node: &Node,
+        fnid: FnId,
+        mut phases: Vec<ColdStartPhase>,
+    ) -> Vec<ColdStartPhase> {
+        let Some(image) = self.help.config().image.as_ref() else {
+            return phases;
+        };
+        let pos = phases
+            .iter()
+            .position(|p| p.phase == ColdStartPhaseKind::ImagePull)
+            .unwrap_or(0);
+        phases.retain(|p| p.phase != ColdStartPhaseKind::ImagePull);
+        let missing = node.image_missing_size(fnid, self);
+        if missing > 0.0 {
+            let bandwidth = self.node_get_speed_btwn(image.registry_node, node.node_id())
+                * image.pull_bandwidth_ratio;
+            // 拉取阶段的 cpu 和内存占用沿用函数原来的拉取阶段，没有时沿用第一个阶段
+            let func = self.func(fnid);
+            let template = func
+                .cold_start_phases
+                .iter()
+                .find(|p| p.phase == ColdStartPhaseKind::ImagePull)
+                .unwrap_or(&func.cold_start_phases[0]);
+            phases.insert(
+                pos,
+                ColdStartPhase {
+                    phase: ColdStartPhaseKind::ImagePull,
+                    frames: ((missing / bandwidth).ceil() as usize).max(1),
+                    cpu: template.cpu,
+                    mem: template.mem,
+                },
+            );
+        }
+        phases
+    }
+}
@@ -1,9 +1,16 @@
 mod actions;
 mod algos;
 mod apis;
+mod app_spec;
+mod cluster;
+mod cold_start;
 mod config;
 mod env_gc;
+mod export;
+mod fault;
 mod fn_dag;
+mod fn_type;
+mod image;
 mod mechanism;
 mod mechanism_conf;
 mod metric;
@@ -18,8 +25,11 @@ mod sim_env;
 mod sim_loop;
 mod sim_run;
 mod sim_timer;
+mod snapshot;
 mod state;
+mod stem_cell;
 mod util;
+mod workload;
 
 use mechanism_conf::ModuleMechConf;
 use std::{env::set_var, time::Duration};
@@ -52,8 +62,12 @@ const NODE_SCORE_CPU_WEIGHT: f32 = 0.5;
 
 const NODE_SCORE_MEM_WEIGHT: f32 = 0.5;
 
+// 未设置集群描述时的默认节点数
 const NODE_CNT: usize = 10;
 
 const CONTAINER_BASIC_MEM: f32 = 199.0;
 
 const NODE_LEFT_MEM_THRESHOLD: f32 = 2500.0;
+
+// 未配置 slo 的应用，关键路径上每个函数允许的帧数
+const SLO_FRAMES_PER_FN: usize = 3;
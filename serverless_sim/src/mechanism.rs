@@ -6,7 +6,7 @@ use std::{
 use crate::{
     actions::ESActionWrapper,
     config::Config,
-    fn_dag::FnId,
+    fn_dag::{FnId, ReplicaId},
     mechanism_conf::{MechConfig, ModuleMechConf},
     node::NodeId,
     request::ReqId,
@@ -26,18 +26,24 @@ use crate::{
 pub struct UpCmd {
     pub nid: NodeId,
     pub fnid: FnId,
+    // 指定扩容的副本，None 时由节点分配新的副本
+    pub replica: Option<ReplicaId>,
 }
 
 #[derive(Clone)]
 pub struct DownCmd {
     pub nid: NodeId,
     pub fnid: FnId,
+    // 指定缩容的副本，None 时缩容节点上id最大的副本
+    pub replica: Option<ReplicaId>,
 }
 
 pub struct ScheCmd {
     pub nid: NodeId,
     pub reqid: ReqId,
     pub fnid: FnId,
+    // 指定执行的副本，None 时由节点选择任务最少的副本
+    pub replica: Option<ReplicaId>,
     // TODO: memlimit
     pub memlimit: Option<f32>,
 }
@@ -53,7 +59,7 @@ impl SameTarget for UpCmd {
 }
 impl SameTarget for DownCmd {
     fn same_target(&self, other: &Self) -> bool {
-        self.fnid == other.fnid && self.nid == other.nid
+        self.fnid == other.fnid && self.nid == other.nid && self.replica == other.replica
     }
 }
 
@@ -93,7 +99,7 @@ pub const SCHE_NAMES: [&'static str; 8] = [
     // "random",
 ];
 pub const SCALE_NUM_NAMES: [&'static str; 4] = ["no", "hpa", "lass", "temp_scaler"];
-pub const SCALE_DOWN_EXEC_NAMES: [&'static str; 1] = ["default"];
+pub const SCALE_DOWN_EXEC_NAMES: [&'static str; 4] = ["default", "ttl", "hist", "greedy_dual"];
 pub const SCALE_UP_EXEC_NAMES: [&'static str; 2] = ["least_task", "no"];
 pub const MECH_NAMES: [&'static str; 3] = ["no_scale", "scale_sche_separated", "scale_sche_joint"];
 pub const FILTER_NAMES: [&'static str; 1] = ["careful_down"];
@@ -166,7 +172,7 @@ impl ConfigNewMec for Config {
             "no_scale" => {
                 let allow_sche = vec!["faasflow", "pass", "fnsche", "random", "greedy", "consistenthash"];
                 let allow_scale_num = vec!["no"];
-                let allow_scale_down_exec = vec!["default"];
+                let allow_scale_down_exec = SCALE_DOWN_EXEC_NAMES.to_vec();
                 let allow_scale_up_exec = vec!["no"];
 
                 if !check_config(
@@ -182,7 +188,7 @@ impl ConfigNewMec for Config {
             "scale_sche_separated" => {
                 let allow_sche = vec!["random", "greedy"];
                 let allow_scale_num = vec!["hpa", "lass", "temp_scaler"];
-                let allow_scale_down_exec = vec!["default"];
+                let allow_scale_down_exec = SCALE_DOWN_EXEC_NAMES.to_vec();
                 let allow_scale_up_exec = vec!["least_task"];
 
                 if !check_config(
@@ -198,7 +204,7 @@ impl ConfigNewMec for Config {
             "scale_sche_joint" => {
                 let allow_sche = vec!["pos", "bp_balance"];
                 let allow_scale_num = vec!["hpa", "lass", "temp_scaler"];
-                let allow_scale_down_exec = vec!["default"];
+                let allow_scale_down_exec = SCALE_DOWN_EXEC_NAMES.to_vec();
                 let allow_scale_up_exec = vec!["least_task"];
                 if !check_config(
                     &self.mech,
@@ -268,7 +274,7 @@ impl Mechanism for MechanismImpl {
         env: &SimEnv,
         raw_action: ESActionWrapper,
     ) -> (Vec<UpCmd>, Vec<DownCmd>, Vec<ScheCmd>) {
-        match &*env.help.config().mech.mech_type().0 {
+        let (up_cmds, mut down_cmds, sche_cmds) = match &*env.help.config().mech.mech_type().0 {
             "no_scale" => self.step_no_scaler(env, raw_action),
             "scale_sche_separated" => self.step_scale_sche_separated(env, raw_action),
 
@@ -280,7 +286,16 @@ impl Mechanism for MechanismImpl {
                     env.help.config().mech.mech_type().0
                 )
             }
-        }
+        };
+
+        // keep-alive 回收空闲容器，这一帧刚被调度了任务的容器不回收
+        let keep_alive_downs = self.scale_down_exec.borrow_mut().exec_keep_alive(env);
+        down_cmds.extend(keep_alive_downs.into_iter().filter(|down| {
+            !sche_cmds
+                .iter()
+                .any(|sche| sche.nid == down.nid && sche.fnid == down.fnid)
+        }));
+        (up_cmds, down_cmds, sche_cmds)
     }
 }
 
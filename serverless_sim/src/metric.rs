@@ -1,5 +1,6 @@
 use crate::{
-    config::Config, fn_dag::FnId, mechanism_conf::ModuleMechConf, sim_env::SimEnv, util::Window,
+    cold_start::StartMode, config::Config, fn_dag::FnId, mechanism_conf::ModuleMechConf,
+    sim_env::SimEnv, util::Window, NODE_CNT,
 };
 use chrono;
 use serde::{Deserialize, Serialize};
@@ -87,6 +88,11 @@ impl MechMetric {
                 // 获取函数节点id
                 let fnid = env.dag_inner(req.dag_i)[fngid];
 
+                // 该函数在本次请求中不执行
+                if !req.fn_active(fnid) {
+                    continue;
+                }
+
                 // 若该函数没有被调度到节点上
                 if req.get_fn_node(fnid) == None {
 
@@ -145,6 +151,30 @@ impl MechMetric {
 pub struct OneFrameMetric {
     // pub frame: usize,
     done_request_count: usize,
+    // 准入控制拒绝的请求数
+    rejected_request_count: usize,
+    // 超时中止的请求数
+    timeout_request_count: usize,
+    // 新建的容器数
+    cold_start_count: usize,
+    // 由预热通用容器特化的容器数
+    stem_cell_start_count: usize,
+    // 从快照恢复的容器数
+    snapshot_restore_count: usize,
+    // 需要拉取镜像的冷启动数
+    image_pull_count: usize,
+    // 拉取的镜像大小
+    image_pull_size: f32,
+    // 故障的节点数
+    node_failure_count: usize,
+    // 因节点故障丢失的任务数
+    lost_task_count: usize,
+    // 因节点故障丢失的已完成运算量
+    lost_work: f32,
+    // 超过重试次数而失败的请求数
+    failed_request_count: usize,
+    // 调度到故障节点而被丢弃的调度命令数
+    sche_on_down_node_count: usize,
 }
 
 impl OneFrameMetric {
@@ -152,15 +182,71 @@ impl OneFrameMetric {
         Self {
             // frame: 0,
             done_request_count: 0,
+            rejected_request_count: 0,
+            timeout_request_count: 0,
+            cold_start_count: 0,
+            stem_cell_start_count: 0,
+            snapshot_restore_count: 0,
+            image_pull_count: 0,
+            image_pull_size: 0.0,
+            node_failure_count: 0,
+            lost_task_count: 0,
+            lost_work: 0.0,
+            failed_request_count: 0,
+            sche_on_down_node_count: 0,
         }
     }
     pub fn on_frame_begin(&mut self) {
         // self.frame += 1;
         self.done_request_count = 0;
+        self.rejected_request_count = 0;
+        self.timeout_request_count = 0;
+        self.cold_start_count = 0;
+        self.stem_cell_start_count = 0;
+        self.snapshot_restore_count = 0;
+        self.image_pull_count = 0;
+        self.image_pull_size = 0.0;
+        self.node_failure_count = 0;
+        self.lost_task_count = 0;
+        self.lost_work = 0.0;
+        self.failed_request_count = 0;
+        self.sche_on_down_node_count = 0;
     }
     pub fn add_done_request(&mut self) {
         self.done_request_count += 1;
     }
+    pub fn add_rejected_request(&mut self) {
+        self.rejected_request_count += 1;
+    }
+    pub fn add_timeout_request(&mut self) {
+        self.timeout_request_count += 1;
+    }
+    pub fn add_cold_start(&mut self, mode: StartMode) {
+        self.cold_start_count += 1;
+        match mode {
+            StartMode::Cold => {}
+            StartMode::StemCell => self.stem_cell_start_count += 1,
+            StartMode::Snapshot => self.snapshot_restore_count += 1,
+        }
+    }
+    pub fn add_image_pull(&mut self, size: f32) {
+        self.image_pull_count += 1;
+        self.image_pull_size += size;
+    }
+    pub fn add_node_failure(&mut self, lost_tasks: usize, lost_work: f32) {
+        self.node_failure_count += 1;
+        self.lost_task_count += lost_tasks;
+        self.lost_work += lost_work;
+    }
+    pub fn add_lost_work(&mut self, lost_work: f32) {
+        self.lost_work += lost_work;
+    }
+    pub fn add_failed_request(&mut self) {
+        self.failed_request_count += 1;
+    }
+    pub fn add_sche_on_down_node(&mut self) {
+        self.sche_on_down_node_count += 1;
+    }
     // pub fn done_request_count(&self) -> usize {
     //     self.done_request_count
     // }
@@ -176,6 +262,29 @@ pub struct Records {
     // 4 req_done_time_std,
     // 5 req_done_time_avg_90p,
     // 6 cost
+    // 7 score
+    // 8 done_req_count
+    // 9 slo_attainment
+    // 10 slo_violation_count
+    // 11 slo_lateness_avg
+    // 12 app_slo
+    // 13 rejected_req_count
+    // 14 timeout_req_count
+    // 15 req_queue_time_avg
+    // 16 req_exec_time_avg
+    // 17 cold_start_count
+    // 18 stem_cell_start_count
+    // 19 snapshot_restore_count
+    // 20 local_snapshot_count
+    // 21 shared_snapshot_count
+    // 22 image_pull_count
+    // 23 image_pull_size
+    // 24 node_failure_count
+    // 25 down_node_count
+    // 26 lost_task_count
+    // 27 lost_work
+    // 28 failed_req_count
+    // 29 sche_on_down_node_count
     pub frames: Vec<Vec<serde_json::Value>>,
 }
 
@@ -188,9 +297,30 @@ const FRAME_IDX_REQ_DONE_TIME_AVG_90P: usize = 5;
 const FRAME_IDX_COST: usize = 6;
 const FRAME_IDX_SCORE: usize = 7;
 const FRAME_IDX_DONE_REQ_COUNT: usize = 8;
+const FRAME_IDX_SLO_ATTAINMENT: usize = 9;
+const FRAME_IDX_SLO_VIOLATION_COUNT: usize = 10;
+const FRAME_IDX_SLO_LATENESS_AVG: usize = 11;
+const FRAME_IDX_APP_SLO: usize = 12;
+const FRAME_IDX_REJECTED_REQ_COUNT: usize = 13;
+const FRAME_IDX_TIMEOUT_REQ_COUNT: usize = 14;
+const FRAME_IDX_REQ_QUEUE_TIME_AVG: usize = 15;
+const FRAME_IDX_REQ_EXEC_TIME_AVG: usize = 16;
+const FRAME_IDX_COLD_START_COUNT: usize = 17;
+const FRAME_IDX_STEM_CELL_START_COUNT: usize = 18;
+const FRAME_IDX_SNAPSHOT_RESTORE_COUNT: usize = 19;
+const FRAME_IDX_LOCAL_SNAPSHOT_COUNT: usize = 20;
+const FRAME_IDX_SHARED_SNAPSHOT_COUNT: usize = 21;
+const FRAME_IDX_IMAGE_PULL_COUNT: usize = 22;
+const FRAME_IDX_IMAGE_PULL_SIZE: usize = 23;
+const FRAME_IDX_NODE_FAILURE_COUNT: usize = 24;
+const FRAME_IDX_DOWN_NODE_COUNT: usize = 25;
+const FRAME_IDX_LOST_TASK_COUNT: usize = 26;
+const FRAME_IDX_LOST_WORK: usize = 27;
+const FRAME_IDX_FAILED_REQ_COUNT: usize = 28;
+const FRAME_IDX_SCHE_ON_DOWN_NODE_COUNT: usize = 29;
 
 // the last + 1
-const FRAME_LEN: usize = 9;
+const FRAME_LEN: usize = 30;
 
 impl Records {
     pub fn new(mut key: String) -> Self {
@@ -246,9 +376,46 @@ impl Records {
         frame[FRAME_IDX_REQ_DONE_TIME_AVG] = sim_env.req_done_time_avg().into();
         frame[FRAME_IDX_REQ_DONE_TIME_STD] = sim_env.req_done_time_std().into();
         frame[FRAME_IDX_REQ_DONE_TIME_AVG_90P] = sim_env.req_done_time_avg_90p().into();
+        frame[FRAME_IDX_REQ_QUEUE_TIME_AVG] = sim_env.req_queue_time_avg().into();
+        frame[FRAME_IDX_REQ_EXEC_TIME_AVG] = sim_env.req_exec_time_avg().into();
         frame[FRAME_IDX_COST] = sim_env.cost_each_req().into();
         frame[FRAME_IDX_SCORE] = sim_env.score().into();
         frame[FRAME_IDX_DONE_REQ_COUNT] = sim_env.help.metric().done_request_count.into();
+        frame[FRAME_IDX_REJECTED_REQ_COUNT] = sim_env.help.metric().rejected_request_count.into();
+        frame[FRAME_IDX_TIMEOUT_REQ_COUNT] = sim_env.help.metric().timeout_request_count.into();
+        frame[FRAME_IDX_COLD_START_COUNT] = sim_env.help.metric().cold_start_count.into();
+        frame[FRAME_IDX_STEM_CELL_START_COUNT] = sim_env.help.metric().stem_cell_start_count.into();
+        frame[FRAME_IDX_SNAPSHOT_RESTORE_COUNT] =
+            sim_env.help.metric().snapshot_restore_count.into();
+        frame[FRAME_IDX_LOCAL_SNAPSHOT_COUNT] = sim_env.local_snapshot_count().into();
+        frame[FRAME_IDX_SHARED_SNAPSHOT_COUNT] = sim_env.core.shared_snapshots().len().into();
+        frame[FRAME_IDX_IMAGE_PULL_COUNT] = sim_env.help.metric().image_pull_count.into();
+        frame[FRAME_IDX_IMAGE_PULL_SIZE] = sim_env.help.metric().image_pull_size.into();
+        frame[FRAME_IDX_NODE_FAILURE_COUNT] = sim_env.help.metric().node_failure_count.into();
+        frame[FRAME_IDX_DOWN_NODE_COUNT] =
+            sim_env.nodes().iter().filter(|n| n.is_down()).count().into();
+        frame[FRAME_IDX_LOST_TASK_COUNT] = sim_env.help.metric().lost_task_count.into();
+        frame[FRAME_IDX_LOST_WORK] = sim_env.help.metric().lost_work.into();
+        frame[FRAME_IDX_FAILED_REQ_COUNT] = sim_env.help.metric().failed_request_count.into();
+        frame[FRAME_IDX_SCHE_ON_DOWN_NODE_COUNT] =
+            sim_env.help.metric().sche_on_down_node_count.into();
+        frame[FRAME_IDX_SLO_ATTAINMENT] = sim_env.slo_attainment().into();
+        frame[FRAME_IDX_SLO_VIOLATION_COUNT] = sim_env.slo_violation_count().into();
+        frame[FRAME_IDX_SLO_LATENESS_AVG] = sim_env.slo_lateness_avg().into();
+        frame[FRAME_IDX_APP_SLO] = sim_env
+            .app_slo_metrics()
+            .iter()
+            .map(|m| {
+                serde_json::json!({
+                    "d": m.dag_i,
+                    "a": m.attainment(),
+                    "v": m.violation_cnt,
+                    "u": m.unfinished_cnt,
+                    "l": m.lateness_avg(),
+                })
+            })
+            .collect::<Vec<_>>()
+            .into();
 
         self.frames.push(frame);
     }
@@ -294,10 +461,29 @@ impl RecordFile {
             request_freq: "".to_owned(),
             dag_type: "".to_owned(),
             cold_start: "".to_owned(),
+            cold_start_phases: None,
             fn_type: "".to_owned(),
-            // app_types: vec![],
+            fn_type_weights: None,
+            app_types: vec![],
+            dag_gen: None,
+            app_spec: None,
             no_log: false,
             mech: ModuleMechConf::new().0,
+            workload: None,
+            workload_record: None,
+            admission: None,
+            req_timeout: None,
+            export: None,
+            exec_var: None,
+            max_replicas_per_node: 1,
+            container_concurrency: None,
+            stem_cell: None,
+            snapshot: None,
+            image: None,
+            node_cnt: NODE_CNT,
+            cluster: None,
+            cluster_spec: None,
+            fault: None,
         };
 
         Some(Self {
@@ -122,7 +122,7 @@ impl ApiHandler for ApiHandlerImpl {
                     if let Some(sim_env) = sim_envs.get(&key) {
                         let mut sim_env = sim_env.lock();
                         // 调用模拟环境的帮助方法来记录指标，并刷新记录
-                        sim_env.help.metric_record().flush(&sim_env);
+                        sim_env.flush_records();
                         // 用新的配置创建一个新的模拟环境实例
                         *sim_env = SimEnv::new(config);
                     } 
@@ -1,14 +1,18 @@
 use std::{
     cell::{Ref, RefCell, RefMut},
     cmp::Ordering,
-    collections::{BTreeSet, HashMap, HashSet},
+    collections::{BTreeMap, BTreeSet, HashMap},
 };
 
 use crate::{
-    fn_dag::{FnContainer, FnContainerState, FnId, Func},
+    cluster::NodeSpec,
+    fn_dag::{FnContainer, FnId, Func, ReplicaId},
+    cold_start::{self, StartMode},
+    image::ImageCache,
     request::ReqId,
     sim_env::SimEnv,
-    util, NODE_CNT, NODE_LEFT_MEM_THRESHOLD, NODE_SCORE_CPU_WEIGHT, NODE_SCORE_MEM_WEIGHT,
+    stem_cell::{self, StemCell},
+    util, NODE_LEFT_MEM_THRESHOLD, NODE_SCORE_CPU_WEIGHT, NODE_SCORE_MEM_WEIGHT,
 };
 
 pub type NodeId = usize;
@@ -34,11 +38,29 @@ pub struct Node {
     // #资源限制：cpu, mem
     pub rsc_limit: NodeRscLimit,
 
-    // 待处理的任务
-    pending_tasks: RefCell<BTreeSet<(ReqId, FnId)>>,
+    // 执行速度倍数，分得的每份 cpu 完成 speed 倍的运算量
+    pub speed: f32,
 
-    // 节点上已有的函数容器
-    pub fn_containers: RefCell<HashMap<FnId, FnContainer>>,
+    // 节点标签，如 zone、arch、tier
+    pub labels: BTreeMap<String, String>,
+
+    // 待处理的任务 -> 指定的容器副本，None 表示由节点选择
+    pending_tasks: RefCell<BTreeMap<(ReqId, FnId), Option<ReplicaId>>>,
+
+    // 节点上已有的函数容器，同一个函数可以有多个副本
+    pub fn_containers: RefCell<HashMap<(FnId, ReplicaId), FnContainer>>,
+
+    // 预热的通用容器，可以被同一运行时的函数特化
+    pub stem_cells: RefCell<Vec<StemCell>>,
+
+    // 节点本地保存了快照的函数
+    pub snapshots: RefCell<BTreeSet<FnId>>,
+
+    // 本地磁盘的镜像缓存
+    pub images: RefCell<ImageCache>,
+
+    // 节点故障时为恢复的帧，None 表示节点正常
+    pub down_until: Option<usize>,
 
     // 使用了的cpu
     pub cpu: f32,
@@ -66,26 +88,45 @@ impl Node {
     pub fn unready_mem(&self) -> f32 {
         *self.mem.borrow()
     }
-    fn new(node_id: NodeId) -> Self {
+    fn new(node_id: NodeId, spec: &NodeSpec) -> Self {
         Self {
             node_id,
             rsc_limit: NodeRscLimit {
-                cpu: 1000.0,
-                mem: 8000.0,
+                cpu: spec.cpu,
+                mem: spec.mem,
             },
+            speed: spec.speed,
+            labels: spec.labels.clone(),
             fn_containers: HashMap::new().into(),
             cpu: 0.0,
             mem: 0.0.into(),
             last_frame_cpu: 0.0,
             frame_run_count: 0,
-            pending_tasks: BTreeSet::new().into(),
+            pending_tasks: BTreeMap::new().into(),
+            stem_cells: Vec::new().into(),
+            snapshots: BTreeSet::new().into(),
+            images: ImageCache::new().into(),
+            down_until: None,
             last_frame_mem: 0.0,
         }
     }
 
-    // 增加任务
-    pub fn add_task(&self, req_id: ReqId, fn_id: FnId) {
-        self.pending_tasks.borrow_mut().insert((req_id, fn_id));
+    // 增加任务，replica 为 None 时由节点选择任务最少的副本
+    pub fn add_task(&self, req_id: ReqId, fn_id: FnId, replica: Option<ReplicaId>) {
+        self.pending_tasks.borrow_mut().insert((req_id, fn_id), replica);
+    }
+
+    // 移除请求在该节点上的任务，包括待处理的任务和容器中运行的任务
+    pub fn remove_task(&self, req_id: ReqId, fn_id: FnId) {
+        self.pending_tasks.borrow_mut().remove(&(req_id, fn_id));
+        for (_, fncon) in self
+            .fn_containers
+            .borrow_mut()
+            .iter_mut()
+            .filter(|((fnid, _), _)| *fnid == fn_id)
+        {
+            fncon.remove_task(req_id);
+        }
     }
 
     
@@ -109,7 +150,6 @@ impl Node {
             && self.left_mem_for_place_container() > func.container_mem()
     }
     pub fn node_id(&self) -> NodeId {
-        assert!(self.node_id < NODE_CNT);
         self.node_id
     }
 
@@ -135,70 +175,109 @@ impl Node {
         self.pending_tasks.borrow().len()
     }
 
+    // 取出节点上所有待处理的任务
+    pub fn take_pending_tasks(&self) -> Vec<(ReqId, FnId)> {
+        std::mem::take(&mut *self.pending_tasks.borrow_mut())
+            .into_keys()
+            .collect()
+    }
+
     // 返回节点上正在运行的任务数量
     pub fn running_task_cnt(&self) -> usize {
         self.fn_containers
             .borrow()
             .iter()
-            .map(|(_, c)| c.req_fn_state.len())
+            .map(|(_, c)| c.req_fn_state.values().map(|t| t.instances).sum::<usize>())
             .sum()
     }
 
-    // 返回指定函数ID的容器的可变引用
-    pub fn container_mut<'a>(&'a self, fnid: FnId) -> Option<RefMut<'a, FnContainer>> {
+    // 返回指定函数ID和副本的容器的可变引用
+    pub fn container_mut<'a>(
+        &'a self,
+        fnid: FnId,
+        replica: ReplicaId,
+    ) -> Option<RefMut<'a, FnContainer>> {
         let b = self.fn_containers.borrow_mut();
-        if !b.contains_key(&fnid) {
+        if !b.contains_key(&(fnid, replica)) {
             return None;
         }
         let res = RefMut::map(b, |map| {
-            map.get_mut(&fnid)
-                .unwrap_or_else(|| panic!("container {} not found", fnid))
+            map.get_mut(&(fnid, replica))
+                .unwrap_or_else(|| panic!("container {} replica {} not found", fnid, replica))
         });
         Some(res)
         // .get_mut(&fnid)
     }
 
-    // 返回指定函数ID的容器的不可变引用
-    pub fn container<'a>(&'a self, fnid: FnId) -> Option<Ref<'a, FnContainer>> {
+    // 返回指定函数ID和副本的容器的不可变引用
+    pub fn container<'a>(&'a self, fnid: FnId, replica: ReplicaId) -> Option<Ref<'a, FnContainer>> {
         let b = self.fn_containers.borrow();
-        if !b.contains_key(&fnid) {
+        if !b.contains_key(&(fnid, replica)) {
             return None;
         }
         let res = Ref::map(b, |map| {
-            map.get(&fnid)
-                .unwrap_or_else(|| panic!("container {} not found", fnid))
+            map.get(&(fnid, replica))
+                .unwrap_or_else(|| panic!("container {} replica {} not found", fnid, replica))
         });
         Some(res)
         // .get_mut(&fnid)
     }
+
+    // 节点上指定函数的所有容器副本，按副本id排序
+    pub fn fn_replicas(&self, fnid: FnId) -> Vec<ReplicaId> {
+        let mut replicas: Vec<ReplicaId> = self
+            .fn_containers
+            .borrow()
+            .keys()
+            .filter(|(f, _)| *f == fnid)
+            .map(|&(_, r)| r)
+            .collect();
+        replicas.sort();
+        replicas
+    }
+
+    // 节点上是否有指定函数的容器
+    pub fn has_fn_container(&self, fnid: FnId) -> bool {
+        self.fn_containers.borrow().keys().any(|(f, _)| *f == fnid)
+    }
+
+    // 节点上指定函数任务最少的副本
+    fn least_task_replica(&self, fnid: FnId) -> Option<ReplicaId> {
+        self.fn_containers
+            .borrow()
+            .iter()
+            .filter(|((f, _), _)| *f == fnid)
+            .min_by_key(|(&(_, r), c)| (c.req_fn_state.len(), r))
+            .map(|(&(_, r), _)| r)
+    }
     // pub fn container<'a>(&'a self, fnid: FnId) -> Option<&'a FnContainer> {
     //     self.fn_containers.get(&fnid)
     // }
 
-    pub fn try_unload_container(&mut self, fnid: FnId, env: &SimEnv) {
-        log::info!("scale down fn {fnid} from node {}", self.node_id());
+    // 缩容指定函数的一个副本，replica 为 None 时缩容id最大的副本
+    pub fn try_unload_container(&mut self, fnid: FnId, replica: Option<ReplicaId>, env: &SimEnv) {
+        log::info!("scale down fn {fnid} replica {replica:?} from node {}", self.node_id());
         // env.set_scale_down_result(fnid, self.node_id());
 
         let nodeid = self.node_id();
-        let Some(cont) = self.fn_containers.borrow_mut().remove(&fnid)else{
+        let Some(replica) = replica.or_else(|| self.fn_replicas(fnid).last().copied()) else {
             log::info!("try_unload_container not found {}", fnid);
             return;
         };
-        
-        env.core
-            .fn_2_nodes_mut()
-            .get_mut(&fnid)
-            .unwrap()
-            .remove(&nodeid);
-        match cont.state() {
-            FnContainerState::Starting { .. } => {
-                *self.mem.borrow_mut() -=
-                    env.func(fnid).cold_start_container_mem_use;
-            }
-            FnContainerState::Running => {
-                *self.mem.borrow_mut() -= env.func(fnid).container_mem();
-            }
+        let Some(cont) = self.fn_containers.borrow_mut().remove(&(fnid, replica)) else {
+            log::info!("try_unload_container not found {} replica {}", fnid, replica);
+            return;
+        };
+
+        let mut fn_2_nodes = env.core.fn_2_nodes_mut();
+        let fn_nodes = fn_2_nodes.get_mut(&fnid).unwrap();
+        let replicas = fn_nodes.get_mut(&nodeid).unwrap();
+        replicas.remove(&replica);
+        if replicas.is_empty() {
+            fn_nodes.remove(&nodeid);
         }
+        drop(fn_2_nodes);
+        *self.mem.borrow_mut() -= cont.container_basic_mem();
         // let fncon = self.fn_containers.borrow_mut().remove(&fnid).unwrap();
         // let con_mem_take = fncon.mem_take(env);
         // // log::info!("unload fn: {fn_id} from node: {node_id}");
@@ -214,36 +293,95 @@ impl Node {
         //     self.func(fn_id).cold_start_container_mem_use;
     }
 
-    // 尝试在节点上加载指定函数ID的容器。如果内存足够且容器不存在，则创建新容器并更新节点状态
-    pub fn try_load_container(&self, fnid: FnId, env: &SimEnv) {
-        if self.container(fnid).is_none() {
-            // try cold start
-            if self.mem_enough_for_container(&env.func(fnid)) {
-                let fncon = FnContainer::new(fnid, self.node_id(), env);
-                let con_mem_take = fncon.mem_take(env);
-                self.fn_containers.borrow_mut().insert(fnid, fncon);
-                // log::info!("expand fn: {fn_id} to node: {node_id}");
-                // 1. 更新 fn 到nodes的map，用于查询fn 对应哪些节点有部署
-                let node_id = self.node_id();
-                env.core
-                    .fn_2_nodes_mut()
-                    .entry(fnid)
-                    .and_modify(|v| {
-                        v.insert(node_id);
-                    })
-                    .or_insert_with(|| {
-                        let mut set = HashSet::new();
-                        set.insert(node_id);
-                        set
-                    });
-
-                // will recalc next frame begin
-                // but we need to add mem to node in this frame because it's new container
-                *self.mem.borrow_mut() += con_mem_take;
-                // self.nodes.borrow_mut()[node_id].mem +=
-                //     self.func(fn_id).cold_start_container_mem_use;
+    // 尝试在节点上加载指定函数ID的容器副本。如果内存足够且副本不存在，则创建新容器并更新节点状态
+    // replica 为 None 时新建一个副本，新建副本时副本数不能超过 max_replicas_per_node
+    // 返回加载的副本id
+    pub fn try_load_container(
+        &self,
+        fnid: FnId,
+        replica: Option<ReplicaId>,
+        env: &SimEnv,
+    ) -> Option<ReplicaId> {
+        // 故障的节点不能加载容器
+        if self.is_down() {
+            return None;
+        }
+        let replicas = self.fn_replicas(fnid);
+        if let Some(r) = replica.filter(|r| replicas.contains(r)) {
+            return Some(r);
+        }
+        if replicas.len() >= env.help.config().max_replicas_per_node {
+            return None;
+        }
+        // 未指定时取最小的未使用的副本id
+        let replica = replica.unwrap_or_else(|| (0..).find(|r| !replicas.contains(r)).unwrap());
+        // try cold start
+        // 有快照时从快照恢复；
+        // 有同一运行时的就绪预热容器时将其特化，跳过已经完成的冷启动阶段，并可以使用它释放的内存
+        let func = env.func(fnid);
+        let stem_runtime = func
+            .runtime
+            .clone()
+            .filter(|rt| self.has_ready_stem_cell(rt));
+        let (cold_start_phases, start_mode) = if let Some(phases) =
+            env.snapshot_restore_phases(self, fnid)
+        {
+            let (_, peak_mem, _) = cold_start::phases_summary(&phases);
+            let left_mem = self.left_mem_for_place_container();
+            if left_mem <= peak_mem || left_mem <= func.container_mem() {
+                return None;
+            }
+            (phases, StartMode::Snapshot)
+        } else if let Some(runtime) = &stem_runtime {
+            let phases = stem_cell::specialize_phases(&func.cold_start_phases);
+            let (_, peak_mem, _) = cold_start::phases_summary(&phases);
+            let left_mem = self.left_mem_for_place_container()
+                + env.help.config().stem_cell.as_ref().unwrap().mem;
+            if left_mem <= peak_mem || left_mem <= func.container_mem() {
+                return None;
+            }
+            self.take_stem_cell(runtime, env);
+            (phases, StartMode::StemCell)
+        } else {
+            if !self.mem_enough_for_container(&func) {
+                return None;
+            }
+            (func.cold_start_phases.clone(), StartMode::Cold)
+        };
+        drop(func);
+        // 快照中已包含容器的文件系统，其他启动方式需要拉取节点上缺少的镜像
+        let cold_start_phases = if start_mode == StartMode::Snapshot {
+            cold_start_phases
+        } else {
+            env.image_pull_phases(self, fnid, cold_start_phases)
+        };
+        env.help.metric_mut().add_cold_start(start_mode);
+        let fncon = FnContainer::new(fnid, replica, self.node_id(), cold_start_phases, env);
+        let con_mem_take = fncon.mem_take(env);
+        self.fn_containers.borrow_mut().insert((fnid, replica), fncon);
+        if start_mode != StartMode::Snapshot {
+            let pulled = self.image_pull(fnid, env);
+            if pulled > 0.0 {
+                env.help.metric_mut().add_image_pull(pulled);
             }
         }
+        // log::info!("expand fn: {fn_id} to node: {node_id}");
+        // 1. 更新 fn 到nodes的map，用于查询fn 对应哪些节点有部署
+        let node_id = self.node_id();
+        env.core
+            .fn_2_nodes_mut()
+            .entry(fnid)
+            .or_default()
+            .entry(node_id)
+            .or_default()
+            .insert(replica);
+
+        // will recalc next frame begin
+        // but we need to add mem to node in this frame because it's new container
+        *self.mem.borrow_mut() += con_mem_take;
+        // self.nodes.borrow_mut()[node_id].mem +=
+        //     self.func(fn_id).cold_start_container_mem_use;
+        Some(replica)
     }
 
     // 尝试加载节点上所有待处理任务的容器
@@ -252,22 +390,48 @@ impl Node {
         // 用于存储已移除的待处理任务
         let mut removed_pending = vec![];
         // 遍历该节点上的所有待处理任务
-        for &(req_id, fnid) in self.pending_tasks.borrow().iter() {
-            // 尝试加载函数容器
-            self.try_load_container(fnid, env);
+        for (&(req_id, fnid), &replica) in self.pending_tasks.borrow().iter() {
+            // 前驱函数因节点故障重新等待调度时，任务继续等待
+            {
+                let req = env.request(req_id);
+                if req
+                    .parent_fns(env, fnid)
+                    .iter()
+                    .any(|p| !req.fn_node.contains_key(p))
+                {
+                    continue;
+                }
+            }
+            // 指定了副本时加载该副本，否则选择任务最少的副本，没有副本时尝试加载新的副本
+            // 指定的副本不存在且副本数已达上限时，改用任务最少的副本
+            let replica = match replica {
+                Some(r)
+                    if !self.fn_replicas(fnid).contains(&r)
+                        && self.fn_replicas(fnid).len()
+                            >= env.help.config().max_replicas_per_node =>
+                {
+                    self.least_task_replica(fnid)
+                }
+                Some(r) => self.try_load_container(fnid, Some(r), env),
+                None => self
+                    .least_task_replica(fnid)
+                    .or_else(|| self.try_load_container(fnid, None, env)),
+            };
 
-            if let Some(mut fncon) = self.container_mut(fnid) {
+            if let Some(mut fncon) = replica.and_then(|r| self.container_mut(fnid, r)) {
                 // add to container
-                fncon.req_fn_state.insert(
-                    req_id,
-                    env.fn_new_fn_running_state(&env.request(req_id), fnid),
-                );
+                let task = env.fn_new_fn_running_state(&env.request(req_id), fnid, self.node_id());
+                fncon.add_task(req_id, task, env);
                 removed_pending.push((req_id, fnid));
             }
         }
         for r in removed_pending {
             self.pending_tasks.borrow_mut().remove(&r);
         }
+        // 放行容器前排队的任务
+        for (_, fncon) in self.fn_containers.borrow_mut().iter_mut() {
+            fncon.dispatch_queued(env);
+        }
     }
 }
 
@@ -276,8 +440,8 @@ impl SimEnv {
     pub fn node_init_node_graph(&self) {
 
         // 初始化一个节点
-        fn _init_one_node(env: &SimEnv, node_id: NodeId) {
-            let node = Node::new(node_id);
+        fn _init_one_node(env: &SimEnv, node_id: NodeId, spec: &NodeSpec) {
+            let node = Node::new(node_id, spec);
             // let node_i = nodecnt;
             env.core.nodes_mut().push(node);
 
@@ -292,11 +456,14 @@ impl SimEnv {
 
         // 初始化节点图
         // # init nodes graph
-        let dim = NODE_CNT;
+        // 按集群描述创建节点
+        let cluster = self.help.config().cluster_conf();
+        let specs = cluster.node_specs();
+        let dim = specs.len();
         *self.core.node2node_connection_count_mut() = vec![vec![0; dim]; dim];
         *self.core.node2node_graph_mut() = vec![vec![0.0; dim]; dim];
-        for i in 0..dim {
-            _init_one_node(self, i);
+        for (i, spec) in specs.into_iter().enumerate() {
+            _init_one_node(self, i, spec);
         }
 
         log::info!("node bandwidth graph: {:?}", self.core.node2node_graph());
@@ -1,19 +1,54 @@
 use std::{
     cell::{Ref, RefMut},
-    collections::{HashMap, HashSet},
+    collections::{BTreeMap, HashMap, HashSet},
 };
 
 use daggy::petgraph::visit::Topo;
+use serde::{Deserialize, Serialize};
 
 use crate::{
     fn_dag::{DagId, FnId},
     node::NodeId,
     sim_env::SimEnv,
-    REQUEST_GEN_FRAME_INTERVAL,
 };
 
 pub type ReqId = usize;
 
+/// 请求到达时抽取的属性，由随机种子和请求 id 决定，负载记录中保存以便回放
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ReqAttrs {
+    pub input_size: f32,
+    pub active_fns: Option<BTreeMap<FnId, Vec<FnId>>>,
+    pub fn_width: BTreeMap<FnId, usize>,
+}
+
+impl ReqAttrs {
+    fn draw(env: &SimEnv, dag_i: DagId, req_id: ReqId) -> Self {
+        let dag = env.dag(dag_i);
+        let mut rng = env.req_rander(req_id);
+        let active_fns = dag
+            .is_conditional()
+            .then(|| dag.draw_active_fns(&mut rng).into_iter().collect());
+        let fn_width = dag
+            .fan_out
+            .iter()
+            .map(|(&gi, fan_out)| (dag.dag_inner[gi], fan_out.draw(&mut rng)))
+            .collect();
+        let input_size = env
+            .help
+            .config()
+            .exec_var
+            .as_ref()
+            .and_then(|e| e.input.as_ref())
+            .map_or(1.0, |d| d.sample(&mut rng));
+        Self {
+            input_size,
+            active_fns,
+            fn_width,
+        }
+    }
+}
+
 // pub struct RequestPlan {
 //     /// 请求id
 //     pub req_id: ReqId,
@@ -50,6 +85,9 @@ pub struct Request {
     // 请求完成的时刻
     pub end_frame: usize,
 
+    // 请求需要在这一帧之前（含）完成，否则违反 SLO
+    pub deadline: usize,
+
     // fn_dag_walker: Topo<NodeIndex, <FnDagInner as Visitable>::Map>,
 
     // current_fn: Option<(FnId, NodeIndex)>,
@@ -57,6 +95,30 @@ pub struct Request {
 
     // fnid-(predict_time, scheduled_prev_fns_cnt, prev_fns_cnt)
     pub fn_predict_prevs_done_time: HashMap<FnId, (f32, usize, usize)>,
+
+    // 条件DAG中本次请求实际执行的函数 -> 触发了它的前驱函数，None 表示执行DAG中的所有函数
+    pub active_fns: Option<HashMap<FnId, Vec<FnId>>>,
+
+    // 动态 map 函数本次请求的并行实例数
+    pub fn_width: HashMap<FnId, usize>,
+
+    // 动态 map 函数的实例所在节点 -> 该节点上的实例数
+    pub fn_instance_nodes: HashMap<FnId, BTreeMap<NodeId, usize>>,
+
+    // 动态 map 函数尚未完成的实例数
+    pub fn_left_instances: HashMap<FnId, usize>,
+
+    // 函数被调度的时刻
+    pub fn_sche_frame: HashMap<FnId, usize>,
+
+    // 请求的输入规模倍数，均值为 1，函数的运算量和输出数据量可以与之相关
+    pub input_size: f32,
+
+    // 函数在容器前排队等待执行的帧数，多个实例时取最大值
+    pub fn_queue_frames: HashMap<FnId, usize>,
+
+    // 函数因节点故障重新调度的次数
+    pub fn_retries: HashMap<FnId, usize>,
 }
 
 impl Request {
@@ -70,8 +132,20 @@ impl Request {
     //     }
     // }
     pub fn new(env: &SimEnv, dag_i: DagId, begin_frame: usize) -> Self {
+        Self::new_with_attrs(env, dag_i, begin_frame, None)
+    }
+
+    // attrs 为 None 时按请求 id 抽取请求属性
+    pub fn new_with_attrs(
+        env: &SimEnv,
+        dag_i: DagId,
+        begin_frame: usize,
+        attrs: Option<ReqAttrs>,
+    ) -> Self {
+        let req_id = env.help.req_next_id();
+        let attrs = attrs.unwrap_or_else(|| ReqAttrs::draw(env, dag_i, req_id));
         let new = Self {
-            req_id: env.help.req_next_id(),
+            req_id,
             dag_i,
             fn_node: HashMap::new(),
             done_fns: HashMap::new(),
@@ -79,9 +153,18 @@ impl Request {
             // current_fn: None,
             begin_frame,
             end_frame: 0,
+            deadline: begin_frame + env.dag(dag_i).slo,
             cur_frame_done: HashSet::new(),
             walk_cnt: 0,
             fn_predict_prevs_done_time: HashMap::new(),
+            active_fns: attrs.active_fns.map(|a| a.into_iter().collect()),
+            fn_width: attrs.fn_width.into_iter().collect(),
+            fn_instance_nodes: HashMap::new(),
+            fn_left_instances: HashMap::new(),
+            fn_sche_frame: HashMap::new(),
+            input_size: attrs.input_size,
+            fn_queue_frames: HashMap::new(),
+            fn_retries: HashMap::new(),
         };
         // new.prepare_next_fn_2_bind_node(&env.dags.borrow()[dag_i].dag);
         // {
@@ -92,9 +175,25 @@ impl Request {
         new
     }
 
+    // 函数在本次请求中是否执行
+    pub fn fn_active(&self, fnid: FnId) -> bool {
+        match &self.active_fns {
+            Some(active) => active.contains_key(&fnid),
+            None => true,
+        }
+    }
+
+    // 本次请求中需要等待的前驱函数，条件DAG中只包含触发了该函数的前驱
+    pub fn parent_fns(&self, env: &SimEnv, fnid: FnId) -> Vec<FnId> {
+        match &self.active_fns {
+            Some(active) => active.get(&fnid).cloned().unwrap_or_default(),
+            None => env.dag(self.dag_i).parent_fns(fnid),
+        }
+    }
+
     // 判断某函数的前驱函数是否已经全部执行完毕
     pub fn parents_all_done(&self, env: &SimEnv, fnid: FnId) -> bool {
-        let ps = env.func(fnid).parent_fns(env);
+        let ps = self.parent_fns(env, fnid);
         for p in &ps {
             if !self.done_fns.contains_key(p) {
                 return false;
@@ -119,6 +218,39 @@ impl Request {
         self.fn_node.get(&fnid).map(|v| *v)
     }
 
+    // 函数在本次请求中的并行实例数，普通函数为 1
+    pub fn fn_width(&self, fnid: FnId) -> usize {
+        self.fn_width.get(&fnid).copied().unwrap_or(1)
+    }
+
+    // 请求到达时抽取的属性
+    pub fn attrs(&self) -> ReqAttrs {
+        ReqAttrs {
+            input_size: self.input_size,
+            active_fns: self
+                .active_fns
+                .as_ref()
+                .map(|a| a.iter().map(|(k, v)| (*k, v.clone())).collect()),
+            fn_width: self.fn_width.iter().map(|(k, v)| (*k, *v)).collect(),
+        }
+    }
+
+    // 函数的实例所在的节点及各节点上的实例数，未调度时为空
+    pub fn fn_nodes(&self, fnid: FnId) -> Vec<(NodeId, usize)> {
+        match self.fn_instance_nodes.get(&fnid) {
+            Some(nodes) => nodes.iter().map(|(&n, &cnt)| (n, cnt)).collect(),
+            None => self.get_fn_node(fnid).map(|n| (n, 1)).into_iter().collect(),
+        }
+    }
+
+    // 函数在指定节点上的实例数
+    pub fn fn_instances_on(&self, fnid: FnId, nodeid: NodeId) -> usize {
+        self.fn_nodes(fnid)
+            .into_iter()
+            .find(|(n, _)| *n == nodeid)
+            .map_or(0, |(_, cnt)| cnt)
+    }
+
     // pub fn prepare_next_fn_2_bind_node(&mut self, g: &FnDagInner) -> Option<(FnId, NodeIndex)> {
     //     let res = self.fn_dag_walker.next(g);
     //     let res = res.map(|i| (g[i], i));
@@ -142,68 +274,163 @@ impl Request {
             self.end_frame = current_frame;
         }
     }
-    // 返回请求对应DAG中节点（函数）的数量
+    // 函数在一个节点上的 cnt 个实例执行完毕，动态 map 函数的所有实例都完成后函数才完成
+    pub fn fn_instances_done(
+        &mut self,
+        env: &SimEnv,
+        fnid: FnId,
+        cnt: usize,
+        current_frame: usize,
+    ) {
+        if let Some(left) = self.fn_left_instances.get_mut(&fnid) {
+            *left -= cnt;
+            if *left > 0 {
+                return;
+            }
+        }
+        self.fn_done(env, fnid, current_frame);
+    }
+    // 返回请求需要执行的函数的数量
     pub fn fn_count(&self, env: &SimEnv) -> usize {
-        env.core.dags()[self.dag_i].dag_inner.node_count()
+        match &self.active_fns {
+            Some(active) => active.len(),
+            None => env.core.dags()[self.dag_i].dag_inner.node_count(),
+        }
     }
     // 判断请求是否已完成
     pub fn is_done(&self, env: &SimEnv) -> bool {
         self.done_fns.len() == self.fn_count(env)
     }
+    // 已完成的请求是否违反 SLO
+    pub fn slo_violated(&self) -> bool {
+        self.end_frame > self.deadline
+    }
+    // 已完成的请求超过 deadline 的帧数
+    pub fn lateness(&self) -> usize {
+        self.end_frame.saturating_sub(self.deadline)
+    }
+    // 关键路径上各函数在容器前排队的帧数之和
+    // 从最后完成的函数出发，沿最晚完成的前驱回溯到起始函数；
+    // 并行分支和 map 实例的排队相互重叠，不在关键路径上的排队不影响端到端延迟
+    pub fn queue_frames(&self, env: &SimEnv) -> usize {
+        let mut frames = 0;
+        let mut cur = self.latest_done_fn(self.done_fns.keys().copied());
+        while let Some(fnid) = cur {
+            frames += self.fn_queue_frames.get(&fnid).copied().unwrap_or(0);
+            cur = self.latest_done_fn(self.parent_fns(env, fnid));
+        }
+        frames
+    }
+    // 最晚完成的函数，完成帧相同时取排队更久的函数，再按函数id，保证结果确定
+    fn latest_done_fn(&self, fns: impl IntoIterator<Item = FnId>) -> Option<FnId> {
+        fns.into_iter()
+            .filter_map(|f| self.done_fns.get(&f).map(|&done| (f, done)))
+            .max_by_key(|&(f, done)| (done, self.fn_queue_frames.get(&f).copied(), f))
+            .map(|(f, _)| f)
+    }
 }
 
 impl SimEnv {
-    // 生成请求
+    // 生成请求，并添加到环境对象的请求映射中
     pub fn req_sim_gen_requests(&self) {
         let env = self;
 
-        // 每 REQUEST_GEN_FRAME_INTERVAL 帧生成一次请求
-        if *env.core.current_frame() % REQUEST_GEN_FRAME_INTERVAL == 0 {
-
-            // 根据负载情况生成请求
-            let scale = 
-            if env.help.config().dag_type_dag() {
-                // log::info!("DAG类型");
-                if env.help.config().request_freq_high() {
-                    30
-                } else if env.help.config().request_freq_middle() {
-                    20
-                } else {
-                    10
-                }
-            } 
-            else {
-                // log::info!("其他类型");
-                if env.help.config().request_freq_high() {
-                    120
-                } else if env.help.config().request_freq_middle() {
-                    75
-                } else {
-                    30
-                }
-            };
-
-            // 根据 scale 的值和环境中存在的应用数量来生成一个随机数，用来确定要生成的请求的数量
-            let req_cnt = env.env_rand_i(scale, scale * env.core.dags().len());
-
-            // 对每一个请求：随机选择一个应用（dag_i）、创建一个新的请求对象、将新请求添加到环境对象的请求映射中
-            for _ in 0..req_cnt {
-                let dag_i = env.env_rand_i(0, env.core.dags().len() - 1);
-
-                // 创建一个请求实例，一个请求相当于就是一个DAG
-                let request = Request::new(env, dag_i, *env.core.current_frame());
-                
-                let req_id = request.req_id;
-                env.core.requests_mut().insert(req_id, request);
+        let reqs = env.req_gen.borrow_mut().gen_reqs(env);
+        if reqs.is_empty() {
+            return;
+        }
+
+        let req_cnt = reqs.len();
+        let mut rejected_cnt = 0;
+        for request in reqs {
+            if env.req_should_reject() {
+                rejected_cnt += 1;
+                env.help.metric_mut().add_rejected_request();
+                env.req_unfinished(&request);
+                env.req_gen.borrow_mut().on_request_done(env, &request);
+                continue;
             }
+            let req_id = request.req_id;
+            env.core.requests_mut().insert(req_id, request);
+        }
+
+        log::info!(
+            "Gen requests {req_cnt} at frame {}, rejected {rejected_cnt}",
+            env.current_frame()
+        );
+    }
 
-            log::info!("Gen requests {req_cnt} at frame {}", env.current_frame());
+    // 新请求是否被准入控制拒绝
+    fn req_should_reject(&self) -> bool {
+        let Some(admission) = &self.help.config().admission else {
+            return false;
+        };
+        if let Some(max_queue) = admission.max_queue {
+            if self.core.requests().len() >= max_queue {
+                return true;
+            }
+        }
+        if let Some(max_util) = admission.max_util {
+            let nodes = self.core.nodes();
+            let util = nodes
+                .iter()
+                .map(|n| n.last_frame_cpu / n.rsc_limit.cpu)
+                .sum::<f32>()
+                / nodes.len() as f32;
+            if util >= max_util {
+                return true;
+            }
+        }
+        false
+    }
+
+    // 中止超时的请求
+    pub fn req_sim_timeout_requests(&self) {
+        let Some(req_timeout) = self.help.config().req_timeout else {
+            return;
+        };
+        let timeout_reqs: Vec<ReqId> = self
+            .core
+            .requests()
+            .iter()
+            .filter(|(_, req)| self.current_frame() - req.begin_frame >= req_timeout)
+            .map(|(req_id, _)| *req_id)
+            .collect();
+        if timeout_reqs.is_empty() {
+            return;
+        }
+        log::info!(
+            "Abort {} timeout requests at frame {}",
+            timeout_reqs.len(),
+            self.current_frame()
+        );
+        for req_id in timeout_reqs {
+            self.abort_request(req_id);
+            self.help.metric_mut().add_timeout_request();
+        }
+    }
+
+    // 请求被拒绝、超时或失败而未完成，计入 SLO 违反
+    fn req_unfinished(&self, req: &Request) {
+        *self.core.unfinished_requests_mut().entry(req.dag_i).or_insert(0) += 1;
+    }
+
+    // 中止请求，移除它在各节点上的待处理任务和运行中的任务
+    pub fn abort_request(&self, req_id: ReqId) {
+        let req = self.core.requests_mut().remove(&req_id).unwrap();
+        self.req_unfinished(&req);
+        for &fnid in req.fn_node.keys() {
+            for (nodeid, _) in req.fn_nodes(fnid) {
+                self.node(nodeid).remove_task(req_id, fnid);
+            }
         }
+        self.req_gen.borrow_mut().on_request_done(self, &req);
     }
 
     pub fn on_request_done(&self, req_id: ReqId) {
         let req = self.core.requests_mut().remove(&req_id).unwrap();
         self.help.metric_mut().add_done_request();
+        self.req_gen.borrow_mut().on_request_done(self, &req);
         self.core.done_requests_mut().push(req);
     }
 
@@ -0,0 +1,101 @@
+use std::collections::{HashMap, HashSet};
+
+use super::ScaleDownExec;
+use crate::{
+    fn_dag::{FnId, ReplicaId},
+    mechanism::DownCmd,
+    node::NodeId,
+    sim_env::SimEnv,
+};
+
+// 默认在节点内存占用超过该比例时开始回收
+const DEFAULT_MEM_THRESHOLD: f32 = 0.8;
+
+/// 参考 FaasCache 的 Greedy-Dual keep-alive：空闲容器一直保留，节点内存占用超过阈值时，
+/// 按优先级 clock + 使用次数 * 冷启动时间 / 内存 从低到高回收空闲容器，
+/// 节点的 clock 更新为被回收容器的优先级，容器被使用时记录当时的 clock。
+/// 配置的 attr 为内存阈值比例，如 {"greedy_dual": "0.7"}，为空时使用默认值
+pub struct GreedyDualScaleDownExec {
+    mem_threshold: f32,
+    node_clock: HashMap<NodeId, f32>,
+    // 容器最近一次忙碌时节点的 clock
+    container_clock: HashMap<(NodeId, FnId, ReplicaId), f32>,
+}
+
+impl GreedyDualScaleDownExec {
+    pub fn new(attr: &str) -> Self {
+        let mem_threshold = if attr.is_empty() {
+            DEFAULT_MEM_THRESHOLD
+        } else {
+            attr.parse().unwrap_or_else(|_| {
+                panic!("greedy_dual attr should be mem threshold rate, got {}", attr)
+            })
+        };
+        assert!(
+            mem_threshold > 0.0 && mem_threshold <= 1.0,
+            "greedy_dual mem threshold should be in (0, 1]"
+        );
+        Self {
+            mem_threshold,
+            node_clock: HashMap::new(),
+            container_clock: HashMap::new(),
+        }
+    }
+}
+
+impl ScaleDownExec for GreedyDualScaleDownExec {
+    // 只在内存不足时回收，忽略 scale_num 的缩容请求
+    fn exec_scale_down(&mut self, _env: &SimEnv, _fnid: FnId, _scale_cnt: usize) -> Vec<DownCmd> {
+        vec![]
+    }
+
+    fn exec_keep_alive(&mut self, env: &SimEnv) -> Vec<DownCmd> {
+        let mut down_cmds = vec![];
+        let mut exist = HashSet::new();
+        for n in env.core.nodes().iter() {
+            let nid = n.node_id();
+            let clock = *self.node_clock.entry(nid).or_insert(0.0);
+
+            // 空闲容器及其优先级
+            let mut idle_containers = vec![];
+            for (&(fnid, replica), c) in n.fn_containers.borrow().iter() {
+                let key = (nid, fnid, replica);
+                if !c.is_idle() {
+                    self.container_clock.insert(key, clock);
+                }
+                let last_clock = *self.container_clock.entry(key).or_insert(clock);
+                exist.insert(key);
+                if c.is_idle() {
+                    let func = env.func(fnid);
+                    let size = func.mem + func.container_mem();
+                    let priority = last_clock
+                        + c.used_times.max(1) as f32 * func.cold_start_time as f32 / size;
+                    idle_containers.push((priority, fnid, replica, c.container_basic_mem()));
+                }
+            }
+            idle_containers.sort_by(|a, b| {
+                a.0.partial_cmp(&b.0)
+                    .unwrap()
+                    .then((a.1, a.2).cmp(&(b.1, b.2)))
+            });
+
+            let mut used_mem = n.unready_mem();
+            let limit = n.rsc_limit.mem * self.mem_threshold;
+            for (priority, fnid, replica, mem) in idle_containers {
+                if used_mem <= limit {
+                    break;
+                }
+                used_mem -= mem;
+                self.node_clock.insert(nid, priority);
+                down_cmds.push(DownCmd {
+                    nid,
+                    fnid,
+                    replica: Some(replica),
+                });
+            }
+        }
+        // 清理已不存在的容器
+        self.container_clock.retain(|k, _| exist.contains(k));
+        down_cmds
+    }
+}
@@ -0,0 +1,139 @@
+use std::collections::HashMap;
+
+use super::ScaleDownExec;
+use crate::{fn_dag::FnId, mechanism::DownCmd, sim_env::SimEnv};
+
+// 默认的直方图范围（帧），也是直方图不可用时的 keep-alive 时长
+const DEFAULT_RANGE: usize = 60;
+// 直方图中的空闲时间样本少于该值时不使用直方图
+const MIN_SAMPLES: usize = 10;
+// 超出直方图范围的样本比例超过该值时不使用直方图
+const MAX_OOB_RATE: f32 = 0.5;
+// keep-alive 取空闲时间的分位数，并留出一定余量
+const KEEP_ALIVE_PERCENTILE: f32 = 0.99;
+const KEEP_ALIVE_MARGIN: f32 = 0.1;
+
+// 一个函数的空闲时间直方图，每一帧一个桶
+struct FnIdleHist {
+    // 函数最近一次有容器忙碌的帧
+    last_busy_frame: Option<usize>,
+    bins: Vec<usize>,
+    // 超出直方图范围的样本数
+    oob_cnt: usize,
+}
+
+impl FnIdleHist {
+    fn new(range: usize) -> Self {
+        Self {
+            last_busy_frame: None,
+            bins: vec![0; range],
+            oob_cnt: 0,
+        }
+    }
+
+    fn record(&mut self, idle: usize) {
+        if idle < self.bins.len() {
+            self.bins[idle] += 1;
+        } else {
+            self.oob_cnt += 1;
+        }
+    }
+
+    // 按直方图计算 keep-alive 时长，样本不足或空闲时间大多超出范围时使用整个范围
+    fn keep_alive(&self) -> usize {
+        let in_range: usize = self.bins.iter().sum();
+        let total = in_range + self.oob_cnt;
+        if total < MIN_SAMPLES || self.oob_cnt as f32 / total as f32 > MAX_OOB_RATE {
+            return self.bins.len();
+        }
+        let target = (total as f32 * KEEP_ALIVE_PERCENTILE).ceil() as usize;
+        let mut cnt = 0;
+        for (idle, bin) in self.bins.iter().enumerate() {
+            cnt += bin;
+            if cnt >= target {
+                return ((idle + 1) as f32 * (1.0 + KEEP_ALIVE_MARGIN)).ceil() as usize;
+            }
+        }
+        self.bins.len()
+    }
+}
+
+/// 参考 Serverless in the Wild 的混合直方图 keep-alive：为每个函数统计两次忙碌之间的空闲时间，
+/// 容器空闲超过空闲时间的 99 分位（加 10% 余量）后回收；样本不足或不具代表性时退化为固定 TTL。
+/// 配置的 attr 为直方图范围（帧），如 {"hist": "120"}，为空时使用默认值
+pub struct HistScaleDownExec {
+    range: usize,
+    fn_hists: HashMap<FnId, FnIdleHist>,
+}
+
+impl HistScaleDownExec {
+    pub fn new(attr: &str) -> Self {
+        let range = if attr.is_empty() {
+            DEFAULT_RANGE
+        } else {
+            attr.parse()
+                .unwrap_or_else(|_| panic!("hist attr should be frame count, got {}", attr))
+        };
+        assert!(range > 0, "hist range should be positive");
+        Self {
+            range,
+            fn_hists: HashMap::new(),
+        }
+    }
+
+    // 用各函数容器最近的忙碌帧更新空闲时间直方图
+    fn update_hists(&mut self, env: &SimEnv) {
+        for func in env.core.fns().iter() {
+            let mut latest_busy = None;
+            env.fn_containers_for_each(func.fn_id, |c| {
+                latest_busy = latest_busy.max(Some(c.last_busy_frame));
+            });
+            let Some(latest_busy) = latest_busy else {
+                continue;
+            };
+            let range = self.range;
+            let hist = self
+                .fn_hists
+                .entry(func.fn_id)
+                .or_insert_with(|| FnIdleHist::new(range));
+            match hist.last_busy_frame {
+                Some(last) if latest_busy > last => {
+                    if latest_busy - last > 1 {
+                        hist.record(latest_busy - last - 1);
+                    }
+                    hist.last_busy_frame = Some(latest_busy);
+                }
+                Some(_) => {}
+                None => hist.last_busy_frame = Some(latest_busy),
+            }
+        }
+    }
+}
+
+impl ScaleDownExec for HistScaleDownExec {
+    // 只按空闲时间回收，忽略 scale_num 的缩容请求
+    fn exec_scale_down(&mut self, _env: &SimEnv, _fnid: FnId, _scale_cnt: usize) -> Vec<DownCmd> {
+        vec![]
+    }
+
+    fn exec_keep_alive(&mut self, env: &SimEnv) -> Vec<DownCmd> {
+        self.update_hists(env);
+        let mut down_cmds = vec![];
+        for n in env.core.nodes().iter() {
+            for (&(fnid, replica), c) in n.fn_containers.borrow().iter() {
+                let keep_alive = self
+                    .fn_hists
+                    .get(&fnid)
+                    .map_or(self.range, |h| h.keep_alive());
+                if c.idle_frames(env).is_some_and(|idle| idle >= keep_alive) {
+                    down_cmds.push(DownCmd {
+                        nid: n.node_id(),
+                        fnid,
+                        replica: Some(replica),
+                    });
+                }
+            }
+        }
+        down_cmds
+    }
+}
@@ -1,6 +1,10 @@
+pub mod greedy_dual;
+pub mod hist;
+pub mod ttl;
+
 use crate::{
     config::Config,
-    fn_dag::FnId,
+    fn_dag::{FnId, ReplicaId},
     mechanism::DownCmd,
     node::{Node, NodeId},
     sim_env::SimEnv,
@@ -11,6 +15,11 @@ use crate::{
 pub trait ScaleDownExec: Send {
     fn exec_scale_down(&mut self, sim_env: &SimEnv, fnid: FnId, scale_cnt: usize) -> Vec<DownCmd>;
 
+    /// 每帧调用一次，按容器空闲时间回收容器（keep-alive），与 scale_num 的决策无关
+    fn exec_keep_alive(&mut self, _sim_env: &SimEnv) -> Vec<DownCmd> {
+        vec![]
+    }
+
     // /// return success scale up cnt
     // fn scale_up(&mut self, sim_env: &SimEnv, fnid: FnId, scale_cnt: usize) -> usize;
 }
@@ -23,6 +32,17 @@ pub fn new_scale_down_exec(c: &Config) -> Option<Box<dyn ScaleDownExec>> {
         "default" => {
             return Some(Box::new(DefaultScaleDownExec));
         }
+        "ttl" => {
+            return Some(Box::new(ttl::TtlScaleDownExec::new(&scale_down_exec_attr)));
+        }
+        "hist" => {
+            return Some(Box::new(hist::HistScaleDownExec::new(&scale_down_exec_attr)));
+        }
+        "greedy_dual" => {
+            return Some(Box::new(greedy_dual::GreedyDualScaleDownExec::new(
+                &scale_down_exec_attr,
+            )));
+        }
         _ => {
             return None;
         }
@@ -32,13 +52,13 @@ pub fn new_scale_down_exec(c: &Config) -> Option<Box<dyn ScaleDownExec>> {
 pub struct DefaultScaleDownExec;
 
 impl DefaultScaleDownExec {
-    fn collect_idle_containers(&self, env: &SimEnv) -> Vec<(NodeId, FnId)> {
+    fn collect_idle_containers(&self, env: &SimEnv) -> Vec<(NodeId, FnId, ReplicaId)> {
         let mut idle_container_node_fn = Vec::new();
 
         for n in env.core.nodes().iter() {
-            for (fnid, fn_ct) in n.fn_containers.borrow().iter() {
+            for (&(fnid, replica), fn_ct) in n.fn_containers.borrow().iter() {
                 if fn_ct.is_idle() {
-                    idle_container_node_fn.push((n.node_id(), *fnid));
+                    idle_container_node_fn.push((n.node_id(), fnid, replica));
                 }
             }
         }
@@ -53,7 +73,7 @@ impl DefaultScaleDownExec {
         mut scale_cnt: usize,
     ) -> Vec<DownCmd> {
         let mut collect_idle_containers = self.collect_idle_containers(env);
-        collect_idle_containers.retain(|&(_nodeid, fnid_)| fnid_ == fnid);
+        collect_idle_containers.retain(|&(_nodeid, fnid_, _replica)| fnid_ == fnid);
 
         if collect_idle_containers.len() < scale_cnt {
             // log::warn!(
@@ -64,7 +84,11 @@ impl DefaultScaleDownExec {
         }
         collect_idle_containers[0..scale_cnt]
             .iter()
-            .map(|&(nodeid, fnid)| DownCmd { nid: nodeid, fnid })
+            .map(|&(nodeid, fnid, replica)| DownCmd {
+                nid: nodeid,
+                fnid,
+                replica: Some(replica),
+            })
             .collect()
     }
 }
@@ -0,0 +1,46 @@
+use super::ScaleDownExec;
+use crate::{fn_dag::FnId, mechanism::DownCmd, sim_env::SimEnv};
+
+// 默认的 keep-alive 时长（帧）
+const DEFAULT_TTL: usize = 10;
+
+/// 固定 TTL 的 keep-alive：容器空闲超过 ttl 帧后被回收，
+/// 配置的 attr 为 ttl 帧数，如 {"ttl": "20"}，为空时使用默认值
+pub struct TtlScaleDownExec {
+    ttl: usize,
+}
+
+impl TtlScaleDownExec {
+    pub fn new(attr: &str) -> Self {
+        let ttl = if attr.is_empty() {
+            DEFAULT_TTL
+        } else {
+            attr.parse()
+                .unwrap_or_else(|_| panic!("ttl attr should be frame count, got {}", attr))
+        };
+        Self { ttl }
+    }
+}
+
+impl ScaleDownExec for TtlScaleDownExec {
+    // 只按空闲时间回收，忽略 scale_num 的缩容请求
+    fn exec_scale_down(&mut self, _env: &SimEnv, _fnid: FnId, _scale_cnt: usize) -> Vec<DownCmd> {
+        vec![]
+    }
+
+    fn exec_keep_alive(&mut self, env: &SimEnv) -> Vec<DownCmd> {
+        let mut down_cmds = vec![];
+        for n in env.core.nodes().iter() {
+            for (&(fnid, replica), c) in n.fn_containers.borrow().iter() {
+                if c.idle_frames(env).is_some_and(|idle| idle >= self.ttl) {
+                    down_cmds.push(DownCmd {
+                        nid: n.node_id(),
+                        fnid,
+                        replica: Some(replica),
+                    });
+                }
+            }
+        }
+        down_cmds
+    }
+}
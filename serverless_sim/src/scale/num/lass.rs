@@ -39,11 +39,8 @@ impl ScaleNum for LassScaleNum {
                     let mut recent_speed_sum = 0.0;
                     let mut recent_speed_cnt = 0;
 
-                    if let Some(nodes) = env.core.fn_2_nodes().get(&fnid) {
-                        nodes.iter().for_each(|&nodeid| {
-                            let node = env.node(nodeid);
-                            let container = node.container(fnid).unwrap();
-
+                    if env.core.fn_2_nodes().contains_key(&fnid) {
+                        env.fn_containers_for_each(fnid, |container| {
                             recent_speed_sum += container.recent_handle_speed();
                             recent_speed_cnt += 1;
                         });
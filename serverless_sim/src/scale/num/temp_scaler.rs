@@ -222,8 +222,8 @@ impl ScaleNum for TempScaleNum {
             while let Some(fngid) = walker.next(&env.dag(req.dag_i).dag_inner) {
                 // 得到该图节点对应的函数
                 let fnid_in_dag = env.dag_inner(req.dag_i)[fngid];
-                // 累加当前函数到达的次数
-                if fnid_in_dag == fnid {
+                // 累加当前函数到达的次数，条件分支中未执行的函数不算到达
+                if fnid_in_dag == fnid && req.fn_active(fnid) {
                     fn_count += 1;
                 }
             }
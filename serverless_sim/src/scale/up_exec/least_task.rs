@@ -14,34 +14,48 @@ impl ScaleUpExec for LeastTaskScaleUpExec {
         let mech_metric = || env.help.mech_metric_mut();
         let mut up_cmds = vec![];
 
-        let mut nodes_no_container = env
+        let max_replicas = env.help.config().max_replicas_per_node;
+        // 各节点上该函数已有的副本数
+        let node_replica_cnt = env
             .nodes()
             .iter()
-            .filter(|n| n.container(fnid).is_none())
-            .map(|n| n.node_id())
+            .map(|n| (n.node_id(), n.fn_replicas(fnid).len()))
             .collect::<Vec<_>>();
 
-        let nodes_with_container_cnt = env.nodes().len() - nodes_no_container.len();
+        let container_cnt: usize = node_replica_cnt.iter().map(|&(_, cnt)| cnt).sum();
+        let mut to_scale_up_cnt = target_cnt.saturating_sub(container_cnt);
 
-        // log::info!("nodes_no_container.len(): {}", nodes_no_container.len());
-        // MARK 修复了一个扩容bug
-        if nodes_with_container_cnt < target_cnt  && nodes_no_container.len() > 0 {
-            let to_scale_up_cnt = std::cmp::min(target_cnt - nodes_with_container_cnt, nodes_no_container.len());
-            // 对不含容器的节点按照其所有任务数量进行降序排序
-            nodes_no_container.sort_by(|&a, &b| {
+        // 按轮次扩容，先为没有副本的节点加载容器，再为副本少的节点增加副本
+        for round in 0..max_replicas {
+            if to_scale_up_cnt == 0 {
+                break;
+            }
+            // 故障的节点不能加载容器
+            let mut nodes_can_load = node_replica_cnt
+                .iter()
+                .filter(|&&(nid, cnt)| cnt <= round && !env.node(nid).is_down())
+                .map(|&(nid, _)| nid)
+                .collect::<Vec<_>>();
+            // log::info!("nodes_can_load.len(): {}", nodes_can_load.len());
+            // MARK 修复了一个扩容bug
+            let cnt = std::cmp::min(to_scale_up_cnt, nodes_can_load.len());
+            // 对可加载容器的节点按照其所有任务数量进行降序排序
+            nodes_can_load.sort_by(|&a, &b| {
                 let acnt = mech_metric().node_task_new_cnt(a);
                 let bcnt = mech_metric().node_task_new_cnt(b);
                 acnt.partial_cmp(&bcnt).unwrap()
             });
             // 反转，即优先选择任务数量最少的节点进行预加载
-            nodes_no_container.reverse();
-            for _ in 0..to_scale_up_cnt {
-                let node_2_load_contaienr = nodes_no_container.pop().unwrap();
+            nodes_can_load.reverse();
+            for _ in 0..cnt {
+                let node_2_load_contaienr = nodes_can_load.pop().unwrap();
                 up_cmds.push(UpCmd {
                     nid: node_2_load_contaienr,
                     fnid,
+                    replica: None,
                 })
             }
+            to_scale_up_cnt -= cnt;
         }
 
         up_cmds
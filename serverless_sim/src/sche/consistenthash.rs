@@ -45,7 +45,7 @@ impl ConsistentHashScheduler {
 
             let mut hasher = DefaultHasher::new();
             fnid.hash(&mut hasher);
-            let mut node_id = hasher.finish() as usize % env.node_cnt();
+            let mut node_id = env.first_up_node_from(hasher.finish() as usize % env.node_cnt());
             let mut node = env.node(node_id);
             let mut node_mem_use_rate = node.unready_mem() / node.rsc_limit.mem;
             let mut nodes_left_mem = env
@@ -63,13 +63,15 @@ impl ConsistentHashScheduler {
                 nid: node_id,
                 reqid: req.req_id,
                 fnid,
+                replica: None,
                 memlimit: None,
             });
             while target_cnt != 0 {
-                if node.container(fnid).is_none() {
+                if !node.has_fn_container(fnid) {
                     scale_up_cmds.push(UpCmd {
                         nid: node_id,
                         fnid,
+                        replica: None,
                     });
                 }
                 node_id = (node_id + 1) % env.node_cnt();
@@ -79,9 +79,12 @@ impl FaasFlowScheduler {
             let mut walker = dag.new_dag_walker();
             while let Some(fnode) = walker.next(&dag.dag_inner) {
                 let fnid = dag.dag_inner[fnode];
+                if !req.fn_active(fnid) {
+                    continue;
+                }
                 let mut hasher = DefaultHasher::new();
                 fnid.hash(&mut hasher);
-                let node_id = hasher.finish() as usize % env.node_cnt(); //thread_rng().gen_range(0..nodes_left_mem.len());
+                let node_id = env.first_up_node_from(hasher.finish() as usize % env.node_cnt()); //thread_rng().gen_range(0..nodes_left_mem.len());
                                                                          // let node_id = (0, nodes_left_mem.len());
                 fn_poses.insert(fnid, node_id);
                 nodes_left_mem[node_id] -= env.func(fnid).container_mem();
@@ -125,8 +128,12 @@ impl FaasFlowScheduler {
             let (nbegin, nend) = dag.dag_inner.edge_endpoints(e).unwrap();
             let fnbegin = dag.dag_inner[nbegin];
             let fnend = dag.dag_inner[nend];
-            let old_node_begin = *fn_poses.get(&fnbegin).unwrap();
-            let old_node_end = *fn_poses.get(&fnend).unwrap();
+            // 本次请求中不执行的函数不参与合并
+            let (Some(&old_node_begin), Some(&old_node_end)) =
+                (fn_poses.get(&fnbegin), fn_poses.get(&fnend))
+            else {
+                return;
+            };
             if old_node_begin == old_node_end {
                 return;
             }
@@ -153,6 +160,7 @@ impl FaasFlowScheduler {
                 nid,
                 reqid: req.req_id,
                 fnid,
+                replica: None,
                 memlimit: None,
             })
             .collect()
@@ -188,6 +196,7 @@ impl Scheduler for FaasFlowScheduler {
                     to_scale_down.push(DownCmd {
                         nid: n.node_id(),
                         fnid: c.fn_id,
+                        replica: Some(c.replica_id),
                     })
                 }
             }
@@ -25,16 +25,17 @@ impl FnScheScheduler {
 impl FnScheScheduler {
     fn select_node_for_fn(&mut self, env: &SimEnv, fnid: FnId) -> NodeId {
         for n in 0..env.node_cnt() {
-            if env.node(n).last_frame_cpu < 0.8 {
+            if !env.node(n).is_down() && env.node(n).last_frame_cpu < 0.8 {
                 // self.fn_default.insert(fnid,n);
                 return n;
             }
         }
+        // 所有节点都故障时任选一个，调度命令会被丢弃
         env.nodes()
             .iter()
+            .filter(|n| !n.is_down())
             .min_by(|a, b| a.all_task_cnt().partial_cmp(&b.all_task_cnt()).unwrap())
-            .unwrap()
-            .node_id()
+            .map_or(0, |n| n.node_id())
     }
 }
 
@@ -56,6 +57,7 @@ impl Scheduler for FnScheScheduler {
                     nid: nodeid,
                     reqid: req.req_id,
                     fnid,
+                    replica: None,
                     memlimit: None,
                 })
             }
@@ -1,7 +1,7 @@
-use std::{borrow::Borrow, cmp::Ordering};
+use std::{borrow::Borrow, cmp::Ordering, collections::HashSet};
 
 use crate::{
-    mechanism::{DownCmd, MechType, ScheCmd, UpCmd}, node::Node, sim_env::SimEnv, sim_run::{schedule_helper, Scheduler}
+    fn_dag::FnId, mechanism::{DownCmd, MechType, ScheCmd, UpCmd}, node::Node, sim_env::SimEnv, sim_run::{schedule_helper, Scheduler}
 };
 
 pub struct GreedyScheduler{
@@ -25,13 +25,18 @@ impl Scheduler for GreedyScheduler {
             );
 
             let all_nodes = env.nodes();
+            // 这一轮中已调度的函数，前驱未调度的函数不能调度
+            let mut scheduled: HashSet<FnId> = req.fn_node.keys().copied().collect();
             //迭代请求中的函数，选择最合适的节点进行调度
             for fnid in fns {
+                if req.parent_fns(env, fnid).iter().any(|p| !scheduled.contains(p)) {
+                    continue;
+                }
                 let nodes = match env.new_mech.mech_type(env) {
                     MechType::ScaleScheSeparated => {
                         all_nodes
                             .iter()
-                            .filter(|n| n.fn_containers.borrow().contains_key(&fnid))
+                            .filter(|n| n.has_fn_container(fnid))
                             .collect::<Vec<_>>()
                     }
                     _ => all_nodes
@@ -57,8 +62,10 @@ impl Scheduler for GreedyScheduler {
                         nid: node.node_id(),
                         reqid: req.req_id,
                         fnid,
+                        replica: None,
                         memlimit: None,
                     });
+                    scheduled.insert(fnid);
                 } else {
                     log::warn!("No suitable node found for function {}", fnid);
                 }
@@ -91,21 +91,23 @@ impl PassScheduler {
         req: &mut Request,
         env: &SimEnv,
     ) {
-        let func = env.func(func_id);
         let nodes = env.core.nodes();
 
-        let func_pres_id = func.parent_fns(env);
+        let func_pres_id = req.parent_fns(env, func_id);
         log::info!("func {} pres {:?}", func_id, func_pres_id);
 
         if func_pres_id.len() == 0 {
             let mut rng = rand::thread_rng();
-            let rand = rng.gen_range(0..nodes.len());
+            let rand = env.first_up_node_from(rng.gen_range(0..nodes.len()));
             schedule_to_map.insert(func_id, rand);
             schedule_to.push((func_id, rand));
         } else {
             let mut min_tran_time_min_tran_node_id: Option<(f32, usize)> = None;
 
             for i in 0..nodes.len() {
+                if nodes[i].is_down() {
+                    continue;
+                }
                 let get_trantime_from_prevs = || -> f32 {
                     let mut t_tran_max = 0.0;
                     // 多个前驱节点的数据传输时间，取最大
@@ -138,11 +140,8 @@ impl PassScheduler {
                 }
             }
 
-            let nodeid = min_tran_time_min_tran_node_id
-                .unwrap_or_else(|| {
-                    panic!("NODES len {}", nodes.len());
-                })
-                .1;
+            // 所有节点都故障时任选一个，调度命令会被丢弃
+            let nodeid = min_tran_time_min_tran_node_id.map_or(0, |min| min.1);
             schedule_to_map.insert(func_id, nodeid);
             schedule_to.push((func_id, nodeid));
         }
@@ -164,6 +163,9 @@ impl PassScheduler {
 
         log::info!("prio order: {:?}", prio_order);
         for (func_id, _fun_prio) in prio_order {
+            if !req.fn_active(*func_id) {
+                continue;
+            }
             self.select_node_for_fn(&mut schedule_to_map, &mut schedule_to, *func_id, req, env);
         }
 
@@ -173,6 +175,7 @@ impl PassScheduler {
                 nid,
                 reqid: req.req_id,
                 fnid,
+                replica: None,
                 memlimit: None,
             })
             .collect()
@@ -33,11 +33,11 @@ impl PosScheduler {
         // let mut schedule_able_fns = vec![];
         'next_fn: while let Some(fngi) = dag_walker.next(&*env.dag_inner(dag_i)) {
             let fnid = env.dag_inner(dag_i)[fngi];
-            if req.fn_node.contains_key(&fnid) {
-                //scheduled
+            if req.fn_node.contains_key(&fnid) || !req.fn_active(fnid) {
+                //scheduled or not executed in this request
                 continue;
             }
-            let parents = env.func(fnid).parent_fns(env);
+            let parents = req.parent_fns(env, fnid);
             for p in &parents {
                 // parent has't been scheduled
                 if !req.fn_node.contains_key(p) {
@@ -116,7 +116,7 @@ impl PosScheduler {
             let mut nodes2select: Vec<NodeId> = env
                 .nodes()
                 .iter()
-                .filter(|n| n.container(fnid).is_some())
+                .filter(|n| n.has_fn_container(fnid))
                 .map(|n| n.node_id())
                 .collect();
             let nodes_with_container_cnt = nodes2select.len();
@@ -162,7 +162,7 @@ impl PosScheduler {
                 .iter()
                 .map(|n| (mech_metric().node_task_new_cnt(*n) as f32))
                 .collect::<Vec<_>>();
-            let fparents = env.func(fnid).parent_fns(env);
+            let fparents = req.parent_fns(env, fnid);
             let nodes_parent_distance = nodes2select
                 .iter()
                 .map(|n| {
@@ -218,6 +218,7 @@ impl PosScheduler {
                 reqid: req.req_id,
                 fnid,
                 nid: best_node,
+                replica: None,
                 memlimit: None,
             })
         }
@@ -34,24 +34,30 @@ impl Scheduler for RandomScheduler {
                     MechType::ScaleScheSeparated => {
                         env.nodes()
                             .iter()
-                            .filter(|n| n.fn_containers.borrow().contains_key(&fnid))
+                            .filter(|n| n.has_fn_container(fnid) && !n.is_down())
                             .map(|n| n.node_id())
                             .collect::<Vec<_>>()
                     }
                     _ => env.nodes()
                             .borrow()
                             .iter()
+                            .filter(|n| !n.is_down())
                             .map(|n| n.node_id())
                             .collect::<Vec<_>>(),
                 };
 
-                let nodeid = nodesid.choose(&mut rand::thread_rng()).expect("No available nodes for scheduling");
+                // 没有可用节点（如节点都故障）时留待之后调度
+                let Some(nodeid) = nodesid.choose(&mut rand::thread_rng()) else {
+                    log::warn!("no available node to schedule req {} fn {}", req.req_id, fnid);
+                    continue;
+                };
                 
                 // 创建调度命令
                 sche_cmds.push(ScheCmd {
                     nid: *nodeid,
                     reqid: req.req_id,
                     fnid,
+                    replica: None,
                     memlimit: None,
                 });
             }
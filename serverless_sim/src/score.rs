@@ -1,4 +1,34 @@
-use crate::sim_env::SimEnv;
+use crate::{fn_dag::DagId, sim_env::SimEnv};
+
+// 一个应用的 SLO 指标
+pub struct AppSloMetric {
+    pub dag_i: DagId,
+    // 已完成的请求数
+    pub done_cnt: usize,
+    // 被拒绝、超时或失败而未完成的请求数
+    pub unfinished_cnt: usize,
+    // 违反 SLO 的请求数，包括未完成的请求
+    pub violation_cnt: usize,
+    // 违反 SLO 的已完成请求超时帧数之和
+    pub lateness_sum: usize,
+}
+
+impl AppSloMetric {
+    pub fn attainment(&self) -> f32 {
+        let total = self.done_cnt + self.unfinished_cnt;
+        if total == 0 {
+            return 1.0;
+        }
+        1.0 - self.violation_cnt as f32 / total as f32
+    }
+    pub fn lateness_avg(&self) -> f32 {
+        let late_cnt = self.violation_cnt - self.unfinished_cnt;
+        if late_cnt == 0 {
+            return 0.0;
+        }
+        self.lateness_sum as f32 / late_cnt as f32
+    }
+}
 
 impl SimEnv {
     /// req_done_avg 平均每个请求处理完的时间 越低越好
@@ -71,6 +101,36 @@ impl SimEnv {
         req_done_times[0..req_done_90p_cnt].iter().sum::<f32>() / (req_done_90p_cnt as f32)
     }
 
+    /// req_queue_time_avg 平均每个请求在关键路径上容器前排队的时间 越低越好
+    pub fn req_queue_time_avg(&self) -> f32 {
+        let done_cnt = self.core.done_requests().len();
+        if done_cnt == 0 {
+            return 0.0;
+        }
+        self.core
+            .done_requests()
+            .iter()
+            .map(|req| req.queue_frames(self) as f32)
+            .sum::<f32>()
+            / done_cnt as f32
+    }
+
+    /// req_exec_time_avg 平均每个请求除去排队之外的处理时间 越低越好
+    pub fn req_exec_time_avg(&self) -> f32 {
+        let done_cnt = self.core.done_requests().len();
+        if done_cnt == 0 {
+            return 0.0;
+        }
+        self.core
+            .done_requests()
+            .iter()
+            .map(|req| {
+                (req.end_frame - req.begin_frame).saturating_sub(req.queue_frames(self)) as f32
+            })
+            .sum::<f32>()
+            / done_cnt as f32
+    }
+
     // /// req_move_on_avg 平均每个请求处理任务推进量
     // fn score_req_move_on_avg(&self) -> f32 {
     //     if self.real_time.requests().len() == 0 {
@@ -91,6 +151,75 @@ impl SimEnv {
     //         .sum::<f32>() / (self.nodes.borrow().len() as f32)
     // }
     
+    /// unfinished_req_count 被拒绝、超时或失败而未完成的请求数
+    pub fn unfinished_req_count(&self) -> usize {
+        self.core.unfinished_requests().values().sum()
+    }
+
+    /// slo_attainment 已完成和未完成的请求中满足 SLO 的比例 越高越好
+    pub fn slo_attainment(&self) -> f32 {
+        let total = self.core.done_requests().len() + self.unfinished_req_count();
+        if total == 0 {
+            return 1.0;
+        }
+        1.0 - self.slo_violation_count() as f32 / total as f32
+    }
+
+    /// slo_violation_count 违反 SLO 的请求数，未完成的请求都视为违反 越低越好
+    pub fn slo_violation_count(&self) -> usize {
+        self.late_done_req_count() + self.unfinished_req_count()
+    }
+
+    // 超过 deadline 才完成的请求数
+    fn late_done_req_count(&self) -> usize {
+        self.core
+            .done_requests()
+            .iter()
+            .filter(|req| req.slo_violated())
+            .count()
+    }
+
+    /// slo_lateness_avg 违反 SLO 的已完成请求平均超时帧数 越低越好
+    pub fn slo_lateness_avg(&self) -> f32 {
+        let violation_cnt = self.late_done_req_count();
+        if violation_cnt == 0 {
+            return 0.0;
+        }
+        self.core
+            .done_requests()
+            .iter()
+            .map(|req| req.lateness() as f32)
+            .sum::<f32>()
+            / violation_cnt as f32
+    }
+
+    // 每个应用的 SLO 指标
+    pub fn app_slo_metrics(&self) -> Vec<AppSloMetric> {
+        let mut metrics: Vec<AppSloMetric> = (0..self.core.dags().len())
+            .map(|dag_i| AppSloMetric {
+                dag_i,
+                done_cnt: 0,
+                unfinished_cnt: 0,
+                violation_cnt: 0,
+                lateness_sum: 0,
+            })
+            .collect();
+        for req in self.core.done_requests().iter() {
+            let m = &mut metrics[req.dag_i];
+            m.done_cnt += 1;
+            if req.slo_violated() {
+                m.violation_cnt += 1;
+                m.lateness_sum += req.lateness();
+            }
+        }
+        for (&dag_i, &cnt) in self.core.unfinished_requests().iter() {
+            let m = &mut metrics[dag_i];
+            m.unfinished_cnt += cnt;
+            m.violation_cnt += cnt;
+        }
+        metrics
+    }
+
     // 已完成请求的平均成本 越低越好
     pub fn cost_each_req(&self) -> f32 {
         if self.core.done_requests().len() == 0 {
@@ -1,6 +1,6 @@
 use std::{
     cell::{Ref, RefCell, RefMut},
-    collections::{BTreeMap, HashMap, HashSet},
+    collections::{BTreeMap, BTreeSet, HashMap},
     time::{Duration, SystemTime, UNIX_EPOCH},
 };
 
@@ -11,7 +11,7 @@ use rand_seeder::Seeder;
 use crate::{
     actions::ESActionWrapper,
     config::Config,
-    fn_dag::{FnDAG, FnId, Func},
+    fn_dag::{DagId, FnDAG, FnId, Func, ReplicaId},
     mechanism::{ConfigNewMec, Mechanism, MechanismImpl},
     metric::{MechMetric, OneFrameMetric, Records},
     node::{Node, NodeId},
@@ -24,7 +24,9 @@ use crate::{
     },
     sche, sim_loop,
     sim_run::Scheduler,
-    util, CONTAINER_BASIC_MEM,
+    util,
+    workload::{new_req_gen, ReqGen},
+    CONTAINER_BASIC_MEM,
 };
 
 pub struct SimEnvHelperState {
@@ -78,9 +80,12 @@ impl SimEnvHelperState {
 }
 
 pub struct SimEnvCoreState {
-    fn_2_nodes: RefCell<HashMap<FnId, HashSet<NodeId>>>,
+    // 函数 -> 部署的节点 -> 节点上的副本
+    fn_2_nodes: RefCell<HashMap<FnId, HashMap<NodeId, BTreeSet<ReplicaId>>>>,
     dags: RefCell<Vec<FnDAG>>,
     fns: RefCell<Vec<Func>>,
+    // 可以被多个应用引用的共享函数
+    shared_fns: RefCell<Vec<FnId>>,
     // 节点间网速图
     node2node_graph: RefCell<Vec<Vec<f32>>>,
     node2node_connection_count: RefCell<Vec<Vec<usize>>>,
@@ -88,6 +93,12 @@ pub struct SimEnvCoreState {
     current_frame: RefCell<usize>,
     requests: RefCell<BTreeMap<ReqId, Request>>,
     done_requests: RefCell<Vec<Request>>,
+    // 应用 -> 被拒绝、超时或失败而未完成的请求数
+    unfinished_requests: RefCell<BTreeMap<DagId, usize>>,
+    // 共享存储中有快照的函数
+    shared_snapshots: RefCell<BTreeSet<FnId>>,
+    // 镜像共享基础层的大小
+    image_base_layers: RefCell<Vec<f32>>,
 }
 impl SimEnvCoreState {
     pub fn dags<'a>(&'a self) -> Ref<'a, Vec<FnDAG>> {
@@ -102,6 +113,12 @@ impl SimEnvCoreState {
     pub fn fns_mut<'a>(&'a self) -> RefMut<'a, Vec<Func>> {
         self.fns.borrow_mut()
     }
+    pub fn shared_fns<'a>(&'a self) -> Ref<'a, Vec<FnId>> {
+        self.shared_fns.borrow()
+    }
+    pub fn shared_fns_mut<'a>(&'a self) -> RefMut<'a, Vec<FnId>> {
+        self.shared_fns.borrow_mut()
+    }
     pub fn node2node_graph<'a>(&'a self) -> Ref<'a, Vec<Vec<f32>>> {
         self.node2node_graph.borrow()
     }
@@ -109,7 +126,7 @@ impl SimEnvCoreState {
         self.node2node_graph.borrow_mut()
     }
 
-    pub fn fn_2_nodes<'a>(&'a self) -> Ref<'a, HashMap<FnId, HashSet<NodeId>>> {
+    pub fn fn_2_nodes<'a>(&'a self) -> Ref<'a, HashMap<FnId, HashMap<NodeId, BTreeSet<ReplicaId>>>> {
         self.fn_2_nodes.borrow()
     }
     pub fn node2node_connection_count<'a>(&'a self) -> Ref<'a, Vec<Vec<usize>>> {
@@ -127,8 +144,17 @@ impl SimEnvCoreState {
     pub fn done_requests<'a>(&'a self) -> Ref<'a, Vec<Request>> {
         self.done_requests.borrow()
     }
+    pub fn unfinished_requests<'a>(&'a self) -> Ref<'a, BTreeMap<DagId, usize>> {
+        self.unfinished_requests.borrow()
+    }
+    pub fn shared_snapshots<'a>(&'a self) -> Ref<'a, BTreeSet<FnId>> {
+        self.shared_snapshots.borrow()
+    }
+    pub fn image_base_layers<'a>(&'a self) -> Ref<'a, Vec<f32>> {
+        self.image_base_layers.borrow()
+    }
 
-    pub fn fn_2_nodes_mut<'a>(&'a self) -> RefMut<'a, HashMap<FnId, HashSet<NodeId>>> {
+    pub fn fn_2_nodes_mut<'a>(&'a self) -> RefMut<'a, HashMap<FnId, HashMap<NodeId, BTreeSet<ReplicaId>>>> {
         self.fn_2_nodes.borrow_mut()
     }
     pub fn node2node_connection_count_mut<'a>(&'a self) -> RefMut<'a, Vec<Vec<usize>>> {
@@ -146,6 +172,15 @@ impl SimEnvCoreState {
     pub fn done_requests_mut<'a>(&'a self) -> RefMut<'a, Vec<Request>> {
         self.done_requests.borrow_mut()
     }
+    pub fn unfinished_requests_mut<'a>(&'a self) -> RefMut<'a, BTreeMap<DagId, usize>> {
+        self.unfinished_requests.borrow_mut()
+    }
+    pub fn shared_snapshots_mut<'a>(&'a self) -> RefMut<'a, BTreeSet<FnId>> {
+        self.shared_snapshots.borrow_mut()
+    }
+    pub fn image_base_layers_mut<'a>(&'a self) -> RefMut<'a, Vec<f32>> {
+        self.image_base_layers.borrow_mut()
+    }
 }
 
 pub struct SimEnvMechanisms {
@@ -187,6 +222,10 @@ impl SimEnvMechanisms {}
 pub struct SimEnv {
     pub recent_use_time: Duration,
     pub rander: RefCell<Pcg64>,
+    // 请求生成专用的随机数流
+    pub workload_rander: RefCell<Pcg64>,
+    // 节点故障注入专用的随机数流
+    pub fault_rander: RefCell<Pcg64>,
     // end time - tasks
     pub timers: RefCell<HashMap<usize, Vec<Box<dyn FnMut(&SimEnv) + Send>>>>,
 
@@ -194,6 +233,9 @@ pub struct SimEnv {
     pub core: SimEnvCoreState,
     // pub mechanisms: SimEnvMechanisms,
     pub new_mech: MechanismImpl,
+
+    // 请求生成器
+    pub req_gen: RefCell<Box<dyn ReqGen>>,
 }
 
 impl SimEnv {
@@ -221,9 +263,13 @@ impl SimEnv {
                 node2node_connection_count: RefCell::new(Vec::new()),
                 requests: RefCell::new(BTreeMap::new()),
                 done_requests: RefCell::new(Vec::new()),
+                unfinished_requests: RefCell::new(BTreeMap::new()),
                 current_frame: RefCell::new(0),
                 fn_2_nodes: RefCell::new(HashMap::new()),
                 fns: RefCell::new(Vec::new()),
+                shared_fns: RefCell::new(Vec::new()),
+                shared_snapshots: RefCell::new(BTreeSet::new()),
+                image_base_layers: RefCell::new(Vec::new()),
             },
             // mechanisms: SimEnvMechanisms {
             //     scale_executor: RefCell::new(DefaultScaleDownExec),
@@ -232,9 +278,16 @@ impl SimEnv {
             //     spec_scale_num: RefCell::new(new_scale_num(&config)),
             // },
             new_mech: config.new_mec().unwrap(),
+            req_gen: RefCell::new(new_req_gen(&config)),
 
             recent_use_time,
             rander: RefCell::new(Seeder::from(&*config.rand_seed).make_rng()),
+            workload_rander: RefCell::new(
+                Seeder::from(&*format!("{}_workload", config.rand_seed)).make_rng(),
+            ),
+            fault_rander: RefCell::new(
+                Seeder::from(&*format!("{}_fault", config.rand_seed)).make_rng(),
+            ),
             timers: HashMap::new().into(),
         };
 
@@ -242,13 +295,20 @@ impl SimEnv {
         newenv.init();
         newenv
     }
+    // 输出指标记录和请求记录
+    pub fn flush_records(&self) {
+        self.help.metric_record().flush(self);
+        self.req_gen.borrow().flush(self);
+        self.export_requests();
+        self.export_snapshots();
+    }
     pub fn reset(&mut self) {
         let config=self.help.config.clone();
         *self=SimEnv::new(config);
     }
     // 初始化方法，进一步设置仿真环境的状态
     fn init(&self) {
-        // 创建 NODE_CNT 个节点并初始化网速图和连接图
+        // 按集群描述创建节点并初始化网速图和连接图
         self.node_init_node_graph();
         // # # init databases
         // # databases_cnt=5
@@ -263,8 +323,13 @@ impl SimEnv {
         // #             break
         // #     self.databases.append(db)
 
+        // 生成函数镜像共享的基础层
+        self.image_gen_base_layers();
+
         // 创建 DAG 实例，并将其加入到 dags 列表中
         self.fn_gen_fn_dags(self);
+        self.export_dags();
+        self.export_cluster();
     }
 
     // 获取当前模拟帧数
@@ -315,8 +380,14 @@ impl SimEnv {
                 .fn_containers
                 .borrow()
                 .iter()
-                .map(|(_, c)| {c.container_basic_mem(self)})
-                .sum();
+                .map(|(_, c)| {c.container_basic_mem()})
+                .sum::<f32>()
+                + n.stem_cells_mem(self)
+                + n.snapshots_mem(self);
+            // 故障的节点视为内存占满，避免被选中
+            if n.is_down() {
+                *n.unready_mem_mut() = n.rsc_limit.mem;
+            }
             
             // 对节点上的每个容器的mem_use和last_frame_mem重设
             for (_, c) in n.fn_containers.borrow_mut().iter_mut() {
@@ -357,6 +428,9 @@ impl SimEnv {
             // 遍历节点上的每个容器
             for (_, c) in n.fn_containers.borrow_mut().iter_mut() {
                 // 更新容器的使用情况
+                if c.this_frame_used || !c.is_idle() {
+                    c.last_busy_frame = self.current_frame();
+                }
                 if c.this_frame_used {
                     c.this_frame_used = false;
                     c.used_times += 1;
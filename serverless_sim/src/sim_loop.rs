@@ -27,6 +27,9 @@ impl SimEnv {
             // 进行帧开始时处理
             self.on_frame_begin();
 
+            // 模拟节点故障和恢复，故障节点上的函数调用重新等待调度
+            self.sim_node_faults();
+
             // 生成新的请求，并添加到环境对象的请求映射中
             self.req_sim_gen_requests();
 
@@ -40,22 +43,30 @@ impl SimEnv {
             // FIXME: should remove conflict cmds
             // TODO: ScheCmd has memlimit
             for sche in sches.iter() {
-                self.schedule_reqfn_on_node(&mut self.request_mut(sche.reqid), sche.fnid, sche.nid);
+                self.schedule_reqfn_on_node(
+                    &mut self.request_mut(sche.reqid),
+                    sche.fnid,
+                    sche.nid,
+                    sche.replica,
+                );
             }
             for down in downs.iter() {
                 self.node_mut(down.nid)
-                    .try_unload_container(down.fnid, self);
+                    .try_unload_container(down.fnid, down.replica, self);
             }
             for up in ups.iter() {
-                self.node(up.nid).try_load_container(up.fnid, self);
+                self.node(up.nid).try_load_container(up.fnid, up.replica, self);
             }
 
             self.sim_run();
 
+            // 中止超时的请求
+            self.req_sim_timeout_requests();
+
             self.on_frame_end();
 
             if self.current_frame() > 1000 {
-                self.help.metric_record_mut().flush(self);
+                self.flush_records();
                 self.reset();
                 break;
             }
@@ -1,12 +1,12 @@
 use std::{
-    collections::{BTreeSet, HashMap},
+    collections::{BTreeMap, BTreeSet, HashMap},
     vec,
 };
 
 use daggy::Walker;
 
 use crate::{
-    fn_dag::{FnContainer, FnContainerState, FnId},
+    fn_dag::{FnContainer, FnContainerState, FnId, ReplicaId},
     mechanism::{DownCmd, ScheCmd, UpCmd},
     node::{Node, NodeId},
     request::{ReqId, Request},
@@ -36,11 +36,11 @@ pub mod schedule_helper {
         // let mut schedule_able_fns = vec![];
         'next_fn: while let Some(fngi) = dag_walker.next(&*env.dag_inner(dag_i)) {
             let fnid = env.dag_inner(dag_i)[fngi];
-            if req.fn_node.contains_key(&fnid) {
-                //scheduled
+            if req.fn_node.contains_key(&fnid) || !req.fn_active(fnid) {
+                //scheduled or not executed in this request
                 continue;
             }
-            let parents = env.func(fnid).parent_fns(env);
+            let parents = req.parent_fns(env, fnid);
             for p in &parents {
                 match config {
                     CollectTaskConfig::PreAllDone => {
@@ -82,6 +82,8 @@ struct TransPath {
     req_id: ReqId,
     /// recv fn
     fn_id: FnId,
+    /// recv container replica
+    replica: ReplicaId,
 }
 
 struct NodeTrans {
@@ -99,7 +101,14 @@ type NodeTransMap = HashMap<(NodeId, NodeId), NodeTrans>;
 
 impl SimEnv {
     // TODO: ScheCmd has memlimit
-    pub fn schedule_reqfn_on_node(&self, req: &mut Request, fnid: FnId, nodeid: NodeId) {
+    // replica 指定调度到的节点上执行的副本，动态 map 函数分散到其它节点的实例由节点选择副本
+    pub fn schedule_reqfn_on_node(
+        &self,
+        req: &mut Request,
+        fnid: FnId,
+        nodeid: NodeId,
+        replica: Option<ReplicaId>,
+    ) {
         // schedule on node
         // let new_fn_running = self.fn_new_fn_running_state(req, fnid);
         // if let Some(container) = self.nodes.borrow_mut()[nodeid].fn_containers.get_mut(&fnid) {
@@ -108,9 +117,57 @@ impl SimEnv {
         // .unwrap_or_else(|| {
         //     panic!("Node {} suppose to have fn {} container.", nodeid, fnid);
         // })
-        self.node_mut(nodeid).add_task(req.req_id, fnid);
+        if !req.fn_active(fnid) {
+            log::warn!("req {} fn {} is not executed in this request", req.req_id, fnid);
+            return;
+        }
+        if req.fn_node.contains_key(&fnid) {
+            log::warn!("req {} fn {} is already scheduled", req.req_id, fnid);
+            return;
+        }
+        // 故障的节点不接收任务，函数留待之后重新调度
+        if self.node(nodeid).is_down() {
+            log::warn!("req {} fn {} scheduled on down node {}", req.req_id, fnid, nodeid);
+            self.help.metric_mut().add_sche_on_down_node();
+            return;
+        }
+        let width = req.fn_width(fnid);
+        if width > 1 {
+            let instance_nodes = self.spread_fn_instances(fnid, nodeid, width);
+            for &n in instance_nodes.keys() {
+                let replica = if n == nodeid { replica } else { None };
+                self.node_mut(n).add_task(req.req_id, fnid, replica);
+            }
+            req.fn_instance_nodes.insert(fnid, instance_nodes);
+            req.fn_left_instances.insert(fnid, width);
+        } else {
+            self.node_mut(nodeid).add_task(req.req_id, fnid, replica);
+        }
 
         req.fn_node.insert(fnid, nodeid);
+        req.fn_sche_frame.insert(fnid, self.current_frame());
+    }
+
+    // 动态 map 函数的实例分配：第一个实例放在调度到的节点，
+    // 其余实例依次轮流放到调度到的节点和已有该函数容器的节点（任务少的优先）上
+    fn spread_fn_instances(
+        &self,
+        fnid: FnId,
+        nodeid: NodeId,
+        width: usize,
+    ) -> BTreeMap<NodeId, usize> {
+        let mut nodes = vec![nodeid];
+        if let Some(fn_nodes) = self.core.fn_2_nodes().get(&fnid) {
+            let mut others: Vec<NodeId> =
+                fn_nodes.keys().copied().filter(|&n| n != nodeid).collect();
+            others.sort_by_key(|&n| (self.node(n).all_task_cnt(), n));
+            nodes.extend(others);
+        }
+        let mut instance_nodes = BTreeMap::new();
+        for i in 0..width {
+            *instance_nodes.entry(nodes[i % nodes.len()]).or_insert(0) += 1;
+        }
+        instance_nodes
     }
 
     // 模拟两个节点之间的数据传输过程
@@ -134,8 +191,13 @@ impl SimEnv {
 
             // 获取 to 节点中的 t 路径所包含的函数的对应容器的可变引用
             let mut container = env_nodes[to]
-                .container_mut(t.fn_id)
-                .unwrap_or_else(|| panic!("node {} has no fn container for fn {}", to, t.fn_id));
+                .container_mut(t.fn_id, t.replica)
+                .unwrap_or_else(|| {
+                    panic!(
+                        "node {} has no fn container for fn {} replica {}",
+                        to, t.fn_id, t.replica
+                    )
+                });
             
             // 将容器在这一帧中的使用情况更新为 true
             container.this_frame_used = true;
@@ -198,9 +260,13 @@ impl SimEnv {
         for node in self.core.nodes_mut().iter_mut() {
             let node_id = node.node_id();
             // 遍历该节点上的所有函数和对应的容器
-            for (fnid, fn_container) in node.fn_containers.borrow_mut().iter_mut() {
-                // 遍历容器上的所有请求和对应的运行状态
+            for (&(fnid, replica), fn_container) in node.fn_containers.borrow_mut().iter_mut() {
+                // 遍历容器上的所有请求和对应的运行状态，排队中的任务还不接收数据
+                let queued = fn_container.queued_reqs();
                 for (req_id, fnrun) in &mut fn_container.req_fn_state {
+                    if queued.contains(req_id) {
+                        continue;
+                    }
                     // 遍历运行状态中，所有需要传输的数据，包括数据发送节点，数据接受总量、已接受量
                     for (send_node, (all, recved)) in &mut fnrun.data_recv {
                         // 数据还没接受完才需要传输
@@ -212,7 +278,8 @@ impl SimEnv {
                             else {
                                 let path = TransPath {
                                     req_id: *req_id,
-                                    fn_id: *fnid,
+                                    fn_id: fnid,
+                                    replica,
                                 };
                                 // log::info!("new one path: {path:?} to node {node_id}");
                                 let send_2_recv =
@@ -254,11 +321,12 @@ impl SimEnv {
     // return true means state move on
     fn sim_compute_container_starting(
         &self,
-        fnid: FnId,
         fc: &mut FnContainer,
         cpu_for_one_task: f32,
     ) {
-        let container_cpu_used = cpu_for_one_task.min(self.func(fnid).cold_start_container_cpu_use);
+        let left_frame = *fc.state().as_starting().unwrap();
+        let phase_cpu = fc.cold_start_phase(left_frame).cpu;
+        let container_cpu_used = cpu_for_one_task.min(phase_cpu);
         fc.set_cpu_use_rate(cpu_for_one_task, container_cpu_used);
 
         fc.starting_left_frame_move_on();
@@ -268,6 +336,7 @@ impl SimEnv {
         &self,
         fnid: FnId,
         container_node_cpu: &mut f32,
+        node_speed: f32,
         fc: &mut FnContainer,
         cpu_for_one_task: f32,
         req_fns_2_run: &BTreeSet<(ReqId, FnId)>,
@@ -285,13 +354,15 @@ impl SimEnv {
             }
             calc_cnt += 1;
 
-            // calc process
-            let used_cpu = cpu_for_one_task.min(fn_running_state.left_calc);
-            fn_running_state.left_calc -= cpu_for_one_task;
+            // calc process，并行的实例各自分得一份cpu，每份cpu完成 node_speed 倍的运算量
+            let instances = fn_running_state.instances as f32;
+            let used_cpu =
+                cpu_for_one_task.min(fn_running_state.left_calc / node_speed) * instances;
+            fn_running_state.left_calc -= cpu_for_one_task * node_speed;
             *container_node_cpu += used_cpu;
 
             // cpu suppose to use
-            container_alloced_cpu += cpu_for_one_task;
+            container_alloced_cpu += cpu_for_one_task * instances;
             // cpu really used
             container_used_cpu += used_cpu;
 
@@ -311,9 +382,9 @@ impl SimEnv {
 
         fc.record_this_frame(self, done_reqs.len(), fc.req_fn_state.len());
         for reqid in done_reqs {
-            fc.req_fn_state.remove(&reqid).unwrap();
+            let task = fc.remove_task(reqid).unwrap();
             let mut req = self.request_mut(reqid);
-            req.fn_done(self, fnid, self.current_frame());
+            req.fn_instances_done(self, fnid, task.instances, self.current_frame());
             if req.is_done(self) {
                 // log::info!("req {} done", reqid);
                 drop(req);
@@ -327,6 +398,8 @@ impl SimEnv {
         n: &mut Node,
     ) -> Option<(BTreeSet<(ReqId, FnId)>, usize, f32)> {
         let mut req_fns_2_run = BTreeSet::new();
+        // 参与计算的实例数，动态 map 函数的每个实例各算一个任务
+        let mut run_instance_cnt = 0;
 
         // collect run fn count, alloc cpu resource equally
         let starting_container_cnt = n
@@ -339,16 +412,21 @@ impl SimEnv {
             })
             .count();
 
-        for (&fnid, fc) in n.fn_containers.borrow_mut().iter_mut() {
+        for (&(fnid, _), fc) in n.fn_containers.borrow_mut().iter_mut() {
             if let FnContainerState::Running { .. } = fc.state() {
                 for (&req_id, fn_running_state) in &fc.req_fn_state {
-                    if fn_running_state.data_recv_done() && n.unready_left_mem() > self.func(fnid).mem {
-                        *n.unready_mem_mut() += self.func(fnid).mem;
+                    if fc.is_queued(req_id) {
+                        continue;
+                    }
+                    let mem = self.func(fnid).mem * fn_running_state.instances as f32;
+                    if fn_running_state.data_recv_done() && n.unready_left_mem() > mem {
+                        *n.unready_mem_mut() += mem;
                         
                         // 增加该节点上被调度该函数的容器的内存使用量
-                        fc.mem_use += self.func(fnid).mem;
+                        fc.mem_use += mem;
 
                         req_fns_2_run.insert((fnid, req_id));
+                        run_instance_cnt += fn_running_state.instances;
                     }
                 }
             }
@@ -360,8 +438,8 @@ impl SimEnv {
         } else {
             // 计算任务数，每个任务平分计算量
             let each_fn_cpu =
-                n.rsc_limit.cpu / ((req_fns_2_run.len() + starting_container_cnt) as f32);
-            n.frame_run_count = req_fns_2_run.len() + starting_container_cnt;
+                n.rsc_limit.cpu / ((run_instance_cnt + starting_container_cnt) as f32);
+            n.frame_run_count = run_instance_cnt + starting_container_cnt;
             Some((req_fns_2_run, starting_container_cnt, each_fn_cpu))
         }
     }
@@ -379,25 +457,32 @@ impl SimEnv {
             if let Some((req_fns_2_run, _starting_container_cnt, cpu_for_one_task)) =
                 self.sim_compute_collect_compute_data(n)
             {
-                for (fnid, fc) in n.fn_containers.borrow_mut().iter_mut() {
+                for (&(fnid, _), fc) in n.fn_containers.borrow_mut().iter_mut() {
                     match fc.state_mut() {
                         FnContainerState::Starting { .. } => {
-                            self.sim_compute_container_starting(*fnid, fc, cpu_for_one_task);
-                            if let FnContainerState::Running = fc.state() {
-                                // starting -> running
-                                *n.unready_mem_mut() -=
-                                    self.func(*fnid).cold_start_container_mem_use;
-                                *n.unready_mem_mut() += self.func(*fnid).container_mem();
+                            // 进入下一个冷启动阶段或 starting -> running 时内存占用改变，
+                            // 节点内存不足以支撑增长时停在当前阶段等待
+                            let mem_before = fc.container_basic_mem();
+                            let mem_grow = fc.starting_next_frame_mem(self) - mem_before;
+                            if mem_grow > 0.0 && mem_grow > n.unready_left_mem() {
+                                fc.set_cpu_use_rate(cpu_for_one_task, 0.0);
+                            } else {
+                                self.sim_compute_container_starting(fc, cpu_for_one_task);
+                                *n.unready_mem_mut() += fc.container_basic_mem() - mem_before;
+                                if fc.state().is_running() {
+                                    self.snapshot_on_container_started(n, fnid);
+                                }
                             }
                         }
                         _ => {}
                     }
                 }
-                for (fnid, fc) in n.fn_containers.borrow_mut().iter_mut() {
+                for (&(fnid, _), fc) in n.fn_containers.borrow_mut().iter_mut() {
                     match fc.state_mut() {
                         FnContainerState::Running => self.sim_compute_container_running(
-                            *fnid,
+                            fnid,
                             &mut n.cpu,
+                            n.speed,
                             fc,
                             cpu_for_one_task,
                             &req_fns_2_run,
@@ -406,14 +491,15 @@ impl SimEnv {
                     }
                 }
             } else {
-                for (fnid, fc) in n.fn_containers.borrow_mut().iter_mut() {
+                for (&(fnid, _), fc) in n.fn_containers.borrow_mut().iter_mut() {
                     match fc.state_mut() {
                         FnContainerState::Starting { .. } => {
                             panic!("should not be starting");
                         }
                         FnContainerState::Running => self.sim_compute_container_running(
-                            *fnid,
+                            fnid,
                             &mut n.cpu,
+                            n.speed,
                             fc,
                             0.0,
                             &BTreeSet::new(),
@@ -425,6 +511,7 @@ impl SimEnv {
     }
 
     pub fn sim_run(&self) {
+        self.sim_stem_cells();
         self.sim_load_container();
         self.sim_transfers();
         self.sim_computes();
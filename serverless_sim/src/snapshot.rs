@@ -0,0 +1,85 @@
+use std::collections::BTreeSet;
+
+use crate::{
+    cold_start::{ColdStartPhase, ColdStartPhaseKind},
+    fn_dag::FnId,
+    node::Node,
+    sim_env::SimEnv,
+};
+
+impl Node {
+    // 本地快照常驻内存的部分
+    pub fn snapshots_mem(&self, env: &SimEnv) -> f32 {
+        let Some(snapshot) = env.help.config().snapshot.as_ref() else {
+            return 0.0;
+        };
+        self.snapshots
+            .borrow()
+            .iter()
+            .map(|&fnid| env.snapshot_size(fnid) * snapshot.local_mem_ratio)
+            .sum()
+    }
+
+    pub fn has_snapshot(&self, fnid: FnId) -> bool {
+        self.snapshots.borrow().contains(&fnid)
+    }
+}
+
+impl SimEnv {
+    // 函数快照的大小，为冷启动的峰值内存乘以 size_ratio
+    pub fn snapshot_size(&self, fnid: FnId) -> f32 {
+        let ratio = self.help.config().snapshot.as_ref().unwrap().size_ratio;
+        self.func(fnid).cold_start_container_mem_use * ratio
+    }
+
+    /// 节点上可以从快照恢复函数容器时返回恢复阶段，
+    /// 优先从本地快照恢复，恢复帧数为快照大小除以对应位置的恢复速度
+    pub fn snapshot_restore_phases(&self, node: &Node, fnid: FnId) -> Option<Vec<ColdStartPhase>> {
+        let snapshot = self.help.config().snapshot.as_ref()?;
+        let speed = if node.has_snapshot(fnid) {
+            snapshot.local_speed
+        } else if self.core.shared_snapshots().contains(&fnid) {
+            snapshot.shared_speed
+        } else {
+            return None;
+        };
+        let size = self.snapshot_size(fnid);
+        Some(vec![ColdStartPhase {
+            phase: ColdStartPhaseKind::Restore,
+            frames: ((size / speed).ceil() as usize).max(1),
+            cpu: snapshot.restore_cpu,
+            mem: size,
+        }])
+    }
+
+    // 容器完成启动后按 store 配置保存还不存在的快照，本地快照常驻内存的部分放不下时不保存
+    pub fn snapshot_on_container_started(&self, node: &Node, fnid: FnId) {
+        let Some(snapshot) = self.help.config().snapshot.as_ref() else {
+            return;
+        };
+        let (local, shared) = snapshot.store_local_shared();
+        if local && !node.has_snapshot(fnid) {
+            let mem = self.snapshot_size(fnid) * snapshot.local_mem_ratio;
+            if mem == 0.0 || node.left_mem_for_place_container() > mem {
+                node.snapshots.borrow_mut().insert(fnid);
+                *node.unready_mem_mut() += mem;
+            }
+        }
+        if shared {
+            self.core.shared_snapshots_mut().insert(fnid);
+        }
+    }
+
+    // 所有节点上的本地快照数
+    pub fn local_snapshot_count(&self) -> usize {
+        self.nodes().iter().map(|n| n.snapshots.borrow().len()).sum()
+    }
+
+    // 节点 -> 节点上有本地快照的函数
+    pub fn local_snapshots(&self) -> Vec<BTreeSet<FnId>> {
+        self.nodes()
+            .iter()
+            .map(|n| n.snapshots.borrow().clone())
+            .collect()
+    }
+}
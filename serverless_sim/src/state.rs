@@ -102,6 +102,8 @@ struct RunningReqFn {
 pub struct SerialNode {
     cpu: f32,
     mem: f32,
+    speed: f32,
+    labels: BTreeMap<String, String>,
     used_cpu: f32,
     used_mem: f32,
     running_req_fns: Vec<RunningReqFn>,
@@ -192,20 +194,22 @@ impl SimEnv {
         let mut serial_nodes = vec![];
         for n in nodes.iter() {
             let mut running_req_fns = vec![];
-            for (fnid, fn_cont) in n.fn_containers.borrow().iter() {
+            for (&(fnid, _), fn_cont) in n.fn_containers.borrow().iter() {
                 fn_cont
                     .req_fn_state
                     .iter()
                     .for_each(|(req_id, _req_fn_state)| {
                         running_req_fns.push(RunningReqFn {
                             req_id: *req_id,
-                            fn_id: *fnid,
+                            fn_id: fnid,
                         });
                     });
             }
             serial_nodes.push(SerialNode {
                 cpu: n.rsc_limit.cpu,
                 mem: n.rsc_limit.mem,
+                speed: n.speed,
+                labels: n.labels.clone(),
                 used_cpu: n.cpu,
                 used_mem: n.unready_mem(),
                 running_req_fns,
@@ -0,0 +1,102 @@
+use crate::{
+    cold_start::{ColdStartPhase, ColdStartPhaseKind},
+    node::Node,
+    sim_env::SimEnv,
+};
+
+/// 节点上的预热通用容器，只绑定运行时，不属于任何函数
+pub struct StemCell {
+    pub runtime: String,
+    // 还需要多少帧预热完成，为 0 时可以被特化
+    left_frame: usize,
+}
+
+impl StemCell {
+    pub fn is_ready(&self) -> bool {
+        self.left_frame == 0
+    }
+}
+
+/// 特化预热容器时跳过已经完成的沙箱创建和运行时初始化阶段，
+/// 只需拉取函数代码和初始化用户代码
+pub fn specialize_phases(phases: &[ColdStartPhase]) -> Vec<ColdStartPhase> {
+    phases
+        .iter()
+        .filter(|p| {
+            p.phase != ColdStartPhaseKind::Sandbox && p.phase != ColdStartPhaseKind::RuntimeInit
+        })
+        .copied()
+        .collect()
+}
+
+impl Node {
+    // 预热容器占用的内存
+    pub fn stem_cells_mem(&self, env: &SimEnv) -> f32 {
+        env.help
+            .config()
+            .stem_cell
+            .as_ref()
+            .map_or(0.0, |c| c.mem * self.stem_cells.borrow().len() as f32)
+    }
+
+    pub fn has_ready_stem_cell(&self, runtime: &str) -> bool {
+        self.stem_cells
+            .borrow()
+            .iter()
+            .any(|c| c.runtime == runtime && c.is_ready())
+    }
+
+    // 取出一个指定运行时的就绪预热容器，并释放它占用的内存
+    pub fn take_stem_cell(&self, runtime: &str, env: &SimEnv) -> bool {
+        let mut cells = self.stem_cells.borrow_mut();
+        let Some(i) = cells
+            .iter()
+            .position(|c| c.runtime == runtime && c.is_ready())
+        else {
+            return false;
+        };
+        cells.remove(i);
+        *self.unready_mem_mut() -= env.help.config().stem_cell.as_ref().unwrap().mem;
+        true
+    }
+}
+
+impl SimEnv {
+    // 为新函数选择运行时，没有配置预热容器池时不选择
+    pub fn fn_gen_runtime(&self) -> Option<String> {
+        let stem_cell = self.help.config().stem_cell.as_ref()?;
+        let runtimes: Vec<&String> = stem_cell.pool.keys().collect();
+        if runtimes.is_empty() {
+            return None;
+        }
+        Some(runtimes[self.env_rand_i(0, runtimes.len())].clone())
+    }
+
+    // 推进预热中的容器，并在内存足够时在后台补充各运行时的预热容器
+    pub fn sim_stem_cells(&self) {
+        let Some(stem_cell) = self.help.config().stem_cell.as_ref() else {
+            return;
+        };
+        for n in self.nodes().iter() {
+            for c in n.stem_cells.borrow_mut().iter_mut() {
+                c.left_frame = c.left_frame.saturating_sub(1);
+            }
+            for (runtime, &size) in &stem_cell.pool {
+                let mut cnt = n
+                    .stem_cells
+                    .borrow()
+                    .iter()
+                    .filter(|c| &c.runtime == runtime)
+                    .count();
+                while cnt < size && n.left_mem_for_place_container() > stem_cell.mem {
+                    n.stem_cells.borrow_mut().push(StemCell {
+                        runtime: runtime.clone(),
+                        left_frame: stem_cell.warm_frames,
+                    });
+                    *n.unready_mem_mut() += stem_cell.mem;
+                    cnt += 1;
+                }
+            }
+        }
+    }
+}
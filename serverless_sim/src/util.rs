@@ -2,8 +2,10 @@ use std::collections::{HashMap, HashSet, VecDeque};
 
 use priority_queue::PriorityQueue;
 use rand::Rng;
+use rand_pcg::Pcg64;
+use rand_seeder::Seeder;
 
-use crate::sim_env::SimEnv;
+use crate::{fn_dag::FnId, request::ReqId, sim_env::SimEnv};
 // use rand::Rng;
 
 // pub fn rand_f(begin: f32, end: f32) -> f32 {
@@ -239,4 +241,66 @@ impl SimEnv {
         let mut rng = self.rander.borrow_mut();
         rng.gen_range(min..max)
     }
+    /// 请求生成使用独立的随机数流，不受调度等机制消耗随机数的影响
+    /// in range of [min, max)
+    pub fn workload_rand_i(&self, min: usize, max: usize) -> usize {
+        let mut rng = self.workload_rander.borrow_mut();
+        rng.gen_range(min..max)
+    }
+    /// in range of [min, max)
+    pub fn workload_rand_f(&self, min: f32, max: f32) -> f32 {
+        let mut rng = self.workload_rander.borrow_mut();
+        rng.gen_range(min..max)
+    }
+    /// 均值为 lambda 的泊松分布采样
+    pub fn workload_rand_poisson(&self, lambda: f32) -> usize {
+        rand_poisson(&mut *self.workload_rander.borrow_mut(), lambda)
+    }
+    /// 故障注入使用独立的随机数流，同一随机种子下各机制遇到的故障序列相同
+    /// in range of [min, max)
+    pub fn fault_rand_f(&self, min: f32, max: f32) -> f32 {
+        let mut rng = self.fault_rander.borrow_mut();
+        rng.gen_range(min..max)
+    }
+    /// 每个请求独立的随机数流，只由随机种子和请求 id 决定，
+    /// 同一负载在不同机制下抽取的结果相同
+    pub fn req_rander(&self, req_id: ReqId) -> Pcg64 {
+        Seeder::from(&*format!("{}_req{}", self.help.config().rand_seed, req_id)).make_rng()
+    }
+    /// 请求中每个函数独立的随机数流，只由随机种子、请求 id 和函数 id 决定
+    pub fn req_fn_rander(&self, req_id: ReqId, fnid: FnId) -> Pcg64 {
+        Seeder::from(&*format!("{}_req{}_fn{}", self.help.config().rand_seed, req_id, fnid))
+            .make_rng()
+    }
+}
+
+/// 均值为 lambda 的泊松分布采样
+pub fn rand_poisson(rng: &mut impl Rng, lambda: f32) -> usize {
+    if lambda <= 0.0 {
+        return 0;
+    }
+    if lambda < 30.0 {
+        // Knuth 算法
+        let l = (-lambda as f64).exp();
+        let mut k = 0;
+        let mut p = 1.0;
+        loop {
+            p *= rng.gen::<f64>();
+            if p <= l {
+                return k;
+            }
+            k += 1;
+        }
+    } else {
+        // lambda 较大时用正态分布近似
+        let z = rand_std_normal(rng);
+        (lambda as f64 + z * (lambda as f64).sqrt()).round().max(0.0) as usize
+    }
+}
+
+/// 标准正态分布采样，Box-Muller 变换
+pub fn rand_std_normal(rng: &mut impl Rng) -> f64 {
+    let u1: f64 = 1.0 - rng.gen::<f64>();
+    let u2: f64 = rng.gen::<f64>();
+    (-2.0 * u1.ln()).sqrt() * (2.0 * std::f64::consts::PI * u2).cos()
 }
@@ -0,0 +1,210 @@
+use super::{pick_dag, ReqGen};
+use crate::{config::WorkloadConfig, fn_dag::DagId, request::Request, sim_env::SimEnv};
+
+/// 按到达过程生成请求，每帧的请求数服从均值为 λ(t) 的泊松分布
+pub struct ArrivalReqGen {
+    conf: WorkloadConfig,
+    // MMPP 当前所处状态
+    mmpp_state: usize,
+}
+
+impl ArrivalReqGen {
+    pub fn new(conf: &WorkloadConfig) -> Self {
+        match conf {
+            WorkloadConfig::Poisson { rate } => {
+                assert!(*rate >= 0.0, "poisson rate should be >= 0");
+            }
+            WorkloadConfig::Mmpp {
+                rates,
+                switch_probs,
+            } => {
+                assert!(!rates.is_empty(), "mmpp rates should not be empty");
+                assert!(
+                    rates.len() == switch_probs.len(),
+                    "mmpp rates and switch_probs should have the same length"
+                );
+            }
+            WorkloadConfig::Diurnal { period, .. } => {
+                assert!(*period > 0, "diurnal period should be > 0");
+            }
+            WorkloadConfig::StepSpike { .. } => {}
+            WorkloadConfig::Trace(_)
+            | WorkloadConfig::Replay { .. }
+            | WorkloadConfig::ClosedLoop { .. } => {
+                panic!("workload {} is not an arrival process", conf.str())
+            }
+        }
+        Self {
+            conf: conf.clone(),
+            mmpp_state: 0,
+        }
+    }
+
+    // 当前帧的平均请求数
+    fn rate(&mut self, env: &SimEnv) -> f32 {
+        let frame = env.current_frame();
+        match &self.conf {
+            WorkloadConfig::Poisson { rate } => *rate,
+            WorkloadConfig::Mmpp {
+                rates,
+                switch_probs,
+            } => {
+                if rates.len() > 1 && env.workload_rand_f(0.0, 1.0) < switch_probs[self.mmpp_state] {
+                    // 等概率切换到其它状态
+                    let next = env.workload_rand_i(0, rates.len() - 1);
+                    self.mmpp_state = if next >= self.mmpp_state {
+                        next + 1
+                    } else {
+                        next
+                    };
+                    log::info!("mmpp switch to state {} at frame {}", self.mmpp_state, frame);
+                }
+                rates[self.mmpp_state]
+            }
+            WorkloadConfig::Diurnal {
+                base,
+                amplitude,
+                period,
+                phase,
+            } => {
+                let angle =
+                    2.0 * std::f32::consts::PI * ((frame + phase) % period) as f32 / *period as f32;
+                (base + amplitude * angle.sin()).max(0.0)
+            }
+            WorkloadConfig::StepSpike { steps, spikes } => {
+                let step = steps
+                    .iter()
+                    .filter(|(begin, _)| *begin <= frame)
+                    .max_by_key(|(begin, _)| *begin)
+                    .map_or(0.0, |(_, rate)| *rate);
+                let spike: f32 = spikes
+                    .iter()
+                    .filter(|(begin, cnt, _)| *begin <= frame && frame < begin + cnt)
+                    .map(|(_, _, rate)| *rate)
+                    .sum();
+                step + spike
+            }
+            WorkloadConfig::Trace(_)
+            | WorkloadConfig::Replay { .. }
+            | WorkloadConfig::ClosedLoop { .. } => unreachable!(),
+        }
+    }
+}
+
+impl ReqGen for ArrivalReqGen {
+    fn gen_reqs(&mut self, env: &SimEnv) -> Vec<Request> {
+        let rate = self.rate(env);
+        let req_cnt = env.workload_rand_poisson(rate);
+        (0..req_cnt)
+            .map(|_| Request::new(env, pick_dag(env), env.current_frame()))
+            .collect()
+    }
+}
+
+/// 每个应用按各自的请求速率（泊松到达）生成请求
+pub struct AppRateReqGen;
+
+impl AppRateReqGen {
+    pub fn new() -> Self {
+        AppRateReqGen {}
+    }
+}
+
+impl ReqGen for AppRateReqGen {
+    fn gen_reqs(&mut self, env: &SimEnv) -> Vec<Request> {
+        let rates: Vec<(DagId, f32)> = env
+            .core
+            .dags()
+            .iter()
+            .filter_map(|d| d.req_rate.map(|r| (d.dag_i, r)))
+            .collect();
+        let mut reqs = vec![];
+        for (dag_i, rate) in rates {
+            for _ in 0..env.workload_rand_poisson(rate) {
+                reqs.push(Request::new(env, dag_i, env.current_frame()));
+            }
+        }
+        reqs
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::test_config;
+
+    fn env_and_gen(workload: serde_json::Value) -> (SimEnv, ArrivalReqGen) {
+        let env = SimEnv::new(test_config(serde_json::json!({ "workload": workload })));
+        let gen = ArrivalReqGen::new(env.help.config().workload.as_ref().unwrap());
+        (env, gen)
+    }
+
+    fn rates_at(env: &SimEnv, gen: &mut ArrivalReqGen, frames: &[usize]) -> Vec<f32> {
+        frames
+            .iter()
+            .map(|f| {
+                *env.core.current_frame_mut() = *f;
+                gen.rate(env)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn poisson_rate() {
+        let (env, mut gen) = env_and_gen(serde_json::json!({ "type": "poisson", "rate": 2.5 }));
+        assert_eq!(rates_at(&env, &mut gen, &[0, 7, 100]), vec![2.5, 2.5, 2.5]);
+    }
+
+    #[test]
+    fn diurnal_rate() {
+        let (env, mut gen) = env_and_gen(serde_json::json!({
+            "type": "diurnal", "base": 10.0, "amplitude": 5.0, "period": 4, "phase": 1
+        }));
+        let rates = rates_at(&env, &mut gen, &[0, 1, 2, 3, 4]);
+        let expect = [15.0, 10.0, 5.0, 10.0, 15.0];
+        for (r, e) in rates.iter().zip(expect) {
+            assert!((r - e).abs() < 1e-4, "rate {} expect {}", r, e);
+        }
+    }
+
+    #[test]
+    fn diurnal_rate_not_negative() {
+        let (env, mut gen) = env_and_gen(serde_json::json!({
+            "type": "diurnal", "base": 1.0, "amplitude": 5.0, "period": 4
+        }));
+        assert_eq!(rates_at(&env, &mut gen, &[3]), vec![0.0]);
+    }
+
+    #[test]
+    fn step_spike_rate() {
+        let (env, mut gen) = env_and_gen(serde_json::json!({
+            "type": "step_spike", "steps": [[2, 1.0], [10, 3.0]], "spikes": [[5, 2, 4.0]]
+        }));
+        assert_eq!(
+            rates_at(&env, &mut gen, &[0, 2, 5, 6, 7, 10]),
+            vec![0.0, 1.0, 5.0, 5.0, 1.0, 3.0]
+        );
+    }
+
+    #[test]
+    fn mmpp_rate() {
+        let (env, mut gen) = env_and_gen(serde_json::json!({
+            "type": "mmpp", "rates": [1.0, 8.0], "switch_probs": [0.0, 0.0]
+        }));
+        assert_eq!(rates_at(&env, &mut gen, &[0, 1, 2]), vec![1.0, 1.0, 1.0]);
+
+        // 每帧必然切换，两个状态交替
+        let (env, mut gen) = env_and_gen(serde_json::json!({
+            "type": "mmpp", "rates": [1.0, 8.0], "switch_probs": [1.0, 1.0]
+        }));
+        assert_eq!(rates_at(&env, &mut gen, &[0, 1, 2]), vec![8.0, 1.0, 8.0]);
+    }
+
+    #[test]
+    #[should_panic(expected = "mmpp rates and switch_probs should have the same length")]
+    fn mmpp_length_mismatch() {
+        env_and_gen(serde_json::json!({
+            "type": "mmpp", "rates": [1.0, 8.0], "switch_probs": [0.1]
+        }));
+    }
+}
@@ -0,0 +1,91 @@
+use std::collections::{BTreeMap, HashMap};
+
+use super::{pick_dag, ReqGen};
+use crate::{
+    request::{ReqId, Request},
+    sim_env::SimEnv,
+};
+
+type UserId = usize;
+
+/// 闭环负载，每个用户同一时间最多有一个未完成的请求
+pub struct ClosedLoopReqGen {
+    users: usize,
+    think_time: f32,
+    think_time_distr: String,
+    // 帧 -> 这一帧结束思考、发出请求的用户，第一次生成请求时初始化
+    wake_users: Option<BTreeMap<usize, Vec<UserId>>>,
+    // 未完成的请求 -> 发出请求的用户
+    req_users: HashMap<ReqId, UserId>,
+}
+
+impl ClosedLoopReqGen {
+    pub fn new(users: usize, think_time: f32, think_time_distr: &str) -> Self {
+        assert!(think_time >= 0.0, "closed loop think_time should be >= 0");
+        match think_time_distr {
+            "exp" | "const" => {}
+            _ => panic!("think_time_distr should be exp or const, got {}", think_time_distr),
+        }
+        Self {
+            users,
+            think_time,
+            think_time_distr: think_time_distr.to_owned(),
+            wake_users: None,
+            req_users: HashMap::new(),
+        }
+    }
+
+    fn rand_think_frames(&self, env: &SimEnv) -> usize {
+        match &*self.think_time_distr {
+            "exp" => (-self.think_time * (1.0 - env.workload_rand_f(0.0, 1.0)).ln()).round() as usize,
+            _ => self.think_time.round() as usize,
+        }
+    }
+
+    // 用户思考 think 帧后发出下一个请求
+    fn user_think(&mut self, user: UserId, from_frame: usize, think: usize) {
+        self.wake_users
+            .as_mut()
+            .unwrap()
+            .entry(from_frame + think)
+            .or_default()
+            .push(user);
+    }
+}
+
+impl ReqGen for ClosedLoopReqGen {
+    fn gen_reqs(&mut self, env: &SimEnv) -> Vec<Request> {
+        let frame = env.current_frame();
+        if self.wake_users.is_none() {
+            // 初始时各用户错开发出第一个请求
+            self.wake_users = Some(BTreeMap::new());
+            for user in 0..self.users {
+                let think = self.rand_think_frames(env);
+                self.user_think(user, frame, think);
+            }
+        }
+
+        let users = self
+            .wake_users
+            .as_mut()
+            .unwrap()
+            .remove(&frame)
+            .unwrap_or_default();
+        let mut reqs = vec![];
+        for user in users {
+            let req = Request::new(env, pick_dag(env), frame);
+            self.req_users.insert(req.req_id, user);
+            reqs.push(req);
+        }
+        reqs
+    }
+
+    fn on_request_done(&mut self, env: &SimEnv, req: &Request) {
+        let Some(user) = self.req_users.remove(&req.req_id) else {
+            return;
+        };
+        // 当前帧已经生成过请求，至少等到下一帧再发出请求
+        let think = self.rand_think_frames(env);
+        self.user_think(user, env.current_frame(), think.max(1));
+    }
+}
@@ -0,0 +1,52 @@
+use super::{pick_dag, ReqGen};
+use crate::{request::Request, sim_env::SimEnv, REQUEST_GEN_FRAME_INTERVAL};
+
+/// 按 request_freq (low, middle, high) 每 REQUEST_GEN_FRAME_INTERVAL 帧生成一批请求
+pub struct FreqReqGen;
+
+impl FreqReqGen {
+    pub fn new() -> Self {
+        FreqReqGen {}
+    }
+}
+
+impl ReqGen for FreqReqGen {
+    fn gen_reqs(&mut self, env: &SimEnv) -> Vec<Request> {
+        let mut reqs = vec![];
+
+        // 每 REQUEST_GEN_FRAME_INTERVAL 帧生成一次请求
+        if env.current_frame() % REQUEST_GEN_FRAME_INTERVAL == 0 {
+            // 根据负载情况生成请求
+            let scale = if env.help.config().dag_type_dag() {
+                if env.help.config().request_freq_high() {
+                    30
+                } else if env.help.config().request_freq_middle() {
+                    20
+                } else {
+                    10
+                }
+            } else {
+                if env.help.config().request_freq_high() {
+                    120
+                } else if env.help.config().request_freq_middle() {
+                    75
+                } else {
+                    30
+                }
+            };
+
+            // 根据 scale 的值和环境中存在的应用数量来生成一个随机数，用来确定要生成的请求的数量，
+            // 取值范围 [scale, scale * 应用数)，只有一个应用时为 scale
+            let req_cnt =
+                env.workload_rand_i(scale, (scale * env.core.dags().len()).max(scale + 1));
+
+            // 对每一个请求：随机选择一个应用（dag_i）并创建一个新的请求对象
+            for _ in 0..req_cnt {
+                let dag_i = pick_dag(env);
+                reqs.push(Request::new(env, dag_i, env.current_frame()));
+            }
+        }
+
+        reqs
+    }
+}
@@ -0,0 +1,73 @@
+pub mod arrival;
+pub mod closed_loop;
+pub mod freq;
+pub mod record;
+pub mod trace;
+
+use crate::{
+    config::{Config, WorkloadConfig},
+    fn_dag::DagId,
+    request::Request,
+    sim_env::SimEnv,
+};
+
+use self::{
+    arrival::{AppRateReqGen, ArrivalReqGen},
+    closed_loop::ClosedLoopReqGen,
+    freq::FreqReqGen,
+    record::{RecordReqGen, ReplayReqGen},
+    trace::TraceReqGen,
+};
+
+pub trait ReqGen: Send {
+    /// 生成当前帧到达的请求
+    fn gen_reqs(&mut self, env: &SimEnv) -> Vec<Request>;
+
+    /// 请求完成、被拒绝或被中止时调用
+    fn on_request_done(&mut self, _env: &SimEnv, _req: &Request) {}
+
+    /// 输出记录时调用
+    fn flush(&self, _env: &SimEnv) {}
+}
+
+pub fn new_req_gen(c: &Config) -> Box<dyn ReqGen> {
+    let gen: Box<dyn ReqGen> = match &c.workload {
+        // 应用配置了请求速率时，每个应用按各自的速率生成请求
+        None if c.app_rate_set() => Box::new(AppRateReqGen::new()),
+        None => Box::new(FreqReqGen::new()),
+        Some(WorkloadConfig::Trace(trace)) => Box::new(TraceReqGen::new(trace)),
+        Some(WorkloadConfig::Replay { path }) => Box::new(ReplayReqGen::new(path)),
+        Some(WorkloadConfig::ClosedLoop {
+            users,
+            think_time,
+            think_time_distr,
+        }) => Box::new(ClosedLoopReqGen::new(*users, *think_time, think_time_distr)),
+        Some(arrival) => Box::new(ArrivalReqGen::new(arrival)),
+    };
+    match &c.workload_record {
+        Some(path) => Box::new(RecordReqGen::new(gen, path)),
+        None => gen,
+    }
+}
+
+/// 按应用权重随机选择一个应用
+pub fn pick_dag(env: &SimEnv) -> DagId {
+    pick_weighted_dag(env).unwrap_or_else(|| env.workload_rand_i(0, env.core.dags().len()))
+}
+
+/// 按应用权重随机选择一个应用，所有应用权重相同时返回 None
+pub fn pick_weighted_dag(env: &SimEnv) -> Option<DagId> {
+    let dags = env.core.dags();
+    if dags.iter().all(|d| d.weight == dags[0].weight) {
+        return None;
+    }
+    let total: f32 = dags.iter().map(|d| d.weight).sum();
+    let mut r = env.workload_rand_f(0.0, total);
+    for dag in dags.iter() {
+        if r < dag.weight {
+            return Some(dag.dag_i);
+        }
+        r -= dag.weight;
+    }
+    Some(dags.len() - 1)
+}
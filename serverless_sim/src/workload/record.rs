@@ -0,0 +1,180 @@
+use std::{collections::BTreeMap, fs, io::Write, path::Path};
+
+use serde::{Deserialize, Serialize};
+
+use super::ReqGen;
+use crate::{
+    fn_dag::DagId,
+    request::{ReqAttrs, Request},
+    sim_env::SimEnv,
+};
+
+/// 一个生成的请求，记录文件中每行一个
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ReqRecord {
+    pub frame: usize,
+    pub dag_i: DagId,
+    // 请求的输入规模、执行的分支和 map 宽度，回放时直接使用，不随随机种子变化；
+    // 旧的记录文件中没有时按请求 id 重新抽取
+    #[serde(default)]
+    pub attrs: Option<ReqAttrs>,
+}
+
+impl ReqRecord {
+    fn new(req: &Request) -> Self {
+        Self {
+            frame: req.begin_frame,
+            dag_i: req.dag_i,
+            attrs: Some(req.attrs()),
+        }
+    }
+
+    fn to_request(&self, env: &SimEnv) -> Request {
+        Request::new_with_attrs(env, self.dag_i, self.frame, self.attrs.clone())
+    }
+}
+
+/// 记录内部生成器生成的所有请求，输出记录时写入文件
+pub struct RecordReqGen {
+    inner: Box<dyn ReqGen>,
+    path: String,
+    records: Vec<ReqRecord>,
+}
+
+impl RecordReqGen {
+    pub fn new(inner: Box<dyn ReqGen>, path: &str) -> Self {
+        Self {
+            inner,
+            path: path.to_owned(),
+            records: vec![],
+        }
+    }
+}
+
+impl ReqGen for RecordReqGen {
+    fn gen_reqs(&mut self, env: &SimEnv) -> Vec<Request> {
+        let reqs = self.inner.gen_reqs(env);
+        self.records.extend(reqs.iter().map(ReqRecord::new));
+        reqs
+    }
+
+    fn on_request_done(&mut self, env: &SimEnv, req: &Request) {
+        self.inner.on_request_done(env, req);
+    }
+
+    fn flush(&self, env: &SimEnv) {
+        self.inner.flush(env);
+        if self.records.is_empty() {
+            log::info!("no generated request, skip workload record");
+            return;
+        }
+        if let Some(dir) = Path::new(&self.path).parent() {
+            fs::create_dir_all(dir).unwrap();
+        }
+        let mut file = fs::File::create(&self.path)
+            .unwrap_or_else(|e| panic!("create workload record {} failed: {}", self.path, e));
+        for r in &self.records {
+            writeln!(file, "{}", serde_json::to_string(r).unwrap()).unwrap();
+        }
+        log::info!("record {} requests to {}", self.records.len(), self.path);
+    }
+}
+
+/// 回放记录文件中的请求
+pub struct ReplayReqGen {
+    frame_records: BTreeMap<usize, Vec<ReqRecord>>,
+}
+
+impl ReplayReqGen {
+    pub fn new(path: &str) -> Self {
+        let content = fs::read_to_string(path)
+            .unwrap_or_else(|e| panic!("read workload record {} failed: {}", path, e));
+        let mut frame_records: BTreeMap<usize, Vec<ReqRecord>> = BTreeMap::new();
+        for line in content.lines().filter(|l| !l.trim().is_empty()) {
+            let r: ReqRecord = serde_json::from_str(line)
+                .unwrap_or_else(|e| panic!("invalid workload record line: {}, {}", line, e));
+            frame_records.entry(r.frame).or_default().push(r);
+        }
+        log::info!("load {} frames of requests from {}", frame_records.len(), path);
+        Self { frame_records }
+    }
+}
+
+impl ReqGen for ReplayReqGen {
+    fn gen_reqs(&mut self, env: &SimEnv) -> Vec<Request> {
+        let dag_cnt = env.core.dags().len();
+        self.frame_records
+            .remove(&env.current_frame())
+            .unwrap_or_default()
+            .iter()
+            .map(|r| {
+                assert!(
+                    r.dag_i < dag_cnt,
+                    "replayed request dag {} not exist, the app config should be the same as recording",
+                    r.dag_i
+                );
+                r.to_request(env)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::test_config;
+
+    // 逐帧生成请求，返回每个请求的记录
+    fn gen_records(env: &SimEnv, frames: usize) -> Vec<String> {
+        let mut records = vec![];
+        for frame in 0..frames {
+            *env.core.current_frame_mut() = frame;
+            let reqs = env.req_gen.borrow_mut().gen_reqs(env);
+            records.extend(
+                reqs.iter()
+                    .map(|r| serde_json::to_string(&ReqRecord::new(r)).unwrap()),
+            );
+        }
+        records
+    }
+
+    #[test]
+    fn record_replay() {
+        let path = std::env::temp_dir().join(format!("record_replay_{}.jsonl", std::process::id()));
+        let path = path.to_str().unwrap();
+
+        let env = SimEnv::new(test_config(serde_json::json!({
+            "dag_type": "dag",
+            "workload_record": path,
+        })));
+        let recorded = gen_records(&env, 20);
+        assert!(!recorded.is_empty());
+        env.req_gen.borrow().flush(&env);
+        assert_eq!(
+            fs::read_to_string(path).unwrap().lines().count(),
+            recorded.len()
+        );
+
+        // 回放不受随机种子影响
+        let env = SimEnv::new(test_config(serde_json::json!({
+            "rand_seed": "replay",
+            "dag_type": "dag",
+            "workload": { "type": "replay", "path": path },
+        })));
+        let replayed = gen_records(&env, 20);
+        fs::remove_file(path).unwrap();
+        assert_eq!(recorded, replayed);
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid workload record line")]
+    fn replay_invalid_line() {
+        let path =
+            std::env::temp_dir().join(format!("replay_invalid_{}.jsonl", std::process::id()));
+        let path = path.to_str().unwrap();
+        fs::write(path, "{\"frame\": 0, \"dag_i\": 0}\nnot json\n").unwrap();
+        let res = std::panic::catch_unwind(|| ReplayReqGen::new(path));
+        fs::remove_file(path).unwrap();
+        std::panic::resume_unwind(res.err().unwrap());
+    }
+}
@@ -0,0 +1,455 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs,
+};
+
+use serde_json::Value;
+
+use super::ReqGen;
+use crate::{config::TraceConfig, fn_dag::DagId, request::Request, sim_env::SimEnv};
+
+/// 轨迹中的一条调用记录，count 次调用均匀散布在 [begin_frame, begin_frame + frame_span) 中
+struct TraceEntry {
+    key: String,
+    begin_frame: usize,
+    frame_span: usize,
+    count: f32,
+}
+
+/// 回放调用轨迹，将轨迹中的应用映射到 dag 上，在对应帧注入请求
+pub struct TraceReqGen {
+    conf: TraceConfig,
+    entries: Vec<TraceEntry>,
+    // 帧 -> 这一帧到达的请求对应的 dag，第一次生成请求时展开
+    frame_dags: Option<BTreeMap<usize, Vec<DagId>>>,
+}
+
+const CSV_APP_COLS: [&str; 2] = ["HashApp", "app"];
+const CSV_FN_COLS: [&str; 3] = ["HashFunction", "function", "func"];
+const CSV_TIMESTAMP_COLS: [&str; 3] = ["timestamp", "end_timestamp", "ts"];
+const CSV_COUNT_COLS: [&str; 2] = ["count", "invocations"];
+
+impl TraceReqGen {
+    pub fn new(conf: &TraceConfig) -> Self {
+        let content = fs::read_to_string(&conf.path)
+            .unwrap_or_else(|e| panic!("read trace file {} failed: {}", conf.path, e));
+        let entries = if conf.path.ends_with(".csv") {
+            Self::parse_csv(conf, &content)
+        } else if conf.path.ends_with(".jsonl") {
+            Self::parse_jsonl(conf, &content)
+        } else {
+            panic!("not support trace file {}, should be .csv or .jsonl", conf.path);
+        };
+        log::info!("load {} trace entries from {}", entries.len(), conf.path);
+
+        Self {
+            conf: conf.clone(),
+            entries,
+            frame_dags: None,
+        }
+    }
+
+    fn key_cols(conf: &TraceConfig) -> &'static [&'static str] {
+        match &*conf.map_by {
+            "app" => &CSV_APP_COLS,
+            "function" => &CSV_FN_COLS,
+            _ => panic!(
+                "trace map_by should be app or function, got {}",
+                conf.map_by
+            ),
+        }
+    }
+
+    // 文件中非空的行及其行号（从 1 开始）
+    fn numbered_lines(content: &str) -> impl Iterator<Item = (usize, &str)> {
+        content
+            .lines()
+            .enumerate()
+            .map(|(i, l)| (i + 1, l))
+            .filter(|(_, l)| !l.trim().is_empty())
+    }
+
+    // 按逗号切分一行，不支持带引号的字段（字段中不能含逗号）
+    fn split_csv_line<'a>(conf: &TraceConfig, lineno: usize, line: &'a str) -> Vec<&'a str> {
+        assert!(
+            !line.contains('"'),
+            "trace file {} line {}: quoted csv fields are not supported",
+            conf.path,
+            lineno
+        );
+        line.split(',').map(|c| c.trim()).collect()
+    }
+
+    // 取一行中的某一列，列数不够时报错
+    fn csv_field<'a>(
+        conf: &TraceConfig,
+        header: &[&str],
+        row: &[&'a str],
+        col: usize,
+        lineno: usize,
+    ) -> &'a str {
+        row.get(col).copied().unwrap_or_else(|| {
+            panic!(
+                "trace file {} line {}: missing column {}, only {} fields",
+                conf.path,
+                lineno,
+                header[col],
+                row.len()
+            )
+        })
+    }
+
+    // 调用次数，空字段为 0
+    fn csv_count(
+        conf: &TraceConfig,
+        header: &[&str],
+        row: &[&str],
+        col: usize,
+        lineno: usize,
+    ) -> f32 {
+        let c = Self::csv_field(conf, header, row, col, lineno);
+        if c.is_empty() {
+            return 0.0;
+        }
+        c.parse().unwrap_or_else(|_| {
+            panic!(
+                "trace file {} line {}: invalid {} {}",
+                conf.path, lineno, header[col], c
+            )
+        })
+    }
+
+    fn parse_csv(conf: &TraceConfig, content: &str) -> Vec<TraceEntry> {
+        let mut lines = Self::numbered_lines(content);
+        let (header_lineno, header_line) = lines
+            .next()
+            .unwrap_or_else(|| panic!("trace file {} is empty", conf.path));
+        let header = Self::split_csv_line(conf, header_lineno, header_line);
+        let find_col = |names: &[&str]| header.iter().position(|c| names.contains(c));
+
+        let key_col = find_col(Self::key_cols(conf)).unwrap_or_else(|| {
+            panic!("trace file {} has no column for {}", conf.path, conf.map_by)
+        });
+        let mut entries = vec![];
+        if let Some(ts_col) = find_col(&CSV_TIMESTAMP_COLS) {
+            // 按时间戳记录
+            let count_col = find_col(&CSV_COUNT_COLS);
+            let mut rows = vec![];
+            for (lineno, line) in lines {
+                let row = Self::split_csv_line(conf, lineno, line);
+                let ts_field = Self::csv_field(conf, &header, &row, ts_col, lineno);
+                let ts: f64 = ts_field.parse().unwrap_or_else(|_| {
+                    panic!(
+                        "trace file {} line {}: invalid timestamp {}",
+                        conf.path, lineno, ts_field
+                    )
+                });
+                let cnt =
+                    count_col.map_or(1.0, |c| Self::csv_count(conf, &header, &row, c, lineno));
+                let key = Self::csv_field(conf, &header, &row, key_col, lineno);
+                rows.push((key.to_owned(), ts, cnt));
+            }
+            Self::timestamp_rows_to_entries(conf, rows, &mut entries);
+        } else {
+            // 按分钟统计，数字列为第几分钟
+            let minute_cols: Vec<(usize, usize)> = header
+                .iter()
+                .enumerate()
+                .filter_map(|(i, c)| c.parse::<usize>().ok().map(|m| (i, m)))
+                .collect();
+            if minute_cols.is_empty() {
+                panic!(
+                    "trace file {} has neither timestamp column nor minute columns",
+                    conf.path
+                );
+            }
+            let first_minute = minute_cols.iter().map(|(_, m)| *m).min().unwrap();
+            for (lineno, line) in lines {
+                let row = Self::split_csv_line(conf, lineno, line);
+                let key = Self::csv_field(conf, &header, &row, key_col, lineno);
+                for &(col, minute) in &minute_cols {
+                    let cnt = Self::csv_count(conf, &header, &row, col, lineno);
+                    if cnt > 0.0 {
+                        entries.push(TraceEntry {
+                            key: key.to_owned(),
+                            begin_frame: (minute - first_minute) * conf.frames_per_minute,
+                            frame_span: conf.frames_per_minute.max(1),
+                            count: cnt,
+                        });
+                    }
+                }
+            }
+        }
+        entries
+    }
+
+    fn parse_jsonl(conf: &TraceConfig, content: &str) -> Vec<TraceEntry> {
+        let key_cols = Self::key_cols(conf);
+        let mut entries = vec![];
+        let mut rows = vec![];
+        for (lineno, line) in Self::numbered_lines(content) {
+            let obj: Value = serde_json::from_str(line).unwrap_or_else(|e| {
+                panic!(
+                    "trace file {} line {}: invalid json, {}",
+                    conf.path, lineno, e
+                )
+            });
+            let key = key_cols
+                .iter()
+                .find_map(|k| obj.get(*k))
+                .map(|v| v.as_str().map_or_else(|| v.to_string(), |s| s.to_owned()))
+                .unwrap_or_else(|| {
+                    panic!(
+                        "trace file {} line {}: no {}",
+                        conf.path, lineno, conf.map_by
+                    )
+                });
+            // 调用次数必须是数字
+            let as_count = |v: &Value| -> f32 {
+                v.as_f64().unwrap_or_else(|| {
+                    panic!(
+                        "trace file {} line {}: invalid count {}",
+                        conf.path, lineno, v
+                    )
+                }) as f32
+            };
+
+            if let Some(counts) = obj.get("counts").and_then(|v| v.as_array()) {
+                // 按分钟统计
+                for (minute, count) in counts.iter().enumerate() {
+                    let count = as_count(count);
+                    if count > 0.0 {
+                        entries.push(TraceEntry {
+                            key: key.clone(),
+                            begin_frame: minute * conf.frames_per_minute,
+                            frame_span: conf.frames_per_minute.max(1),
+                            count,
+                        });
+                    }
+                }
+            } else {
+                let ts = CSV_TIMESTAMP_COLS
+                    .iter()
+                    .find_map(|k| obj.get(*k))
+                    .map(|v| {
+                        v.as_f64().unwrap_or_else(|| {
+                            panic!(
+                                "trace file {} line {}: invalid timestamp {}",
+                                conf.path, lineno, v
+                            )
+                        })
+                    })
+                    .unwrap_or_else(|| {
+                        panic!(
+                            "trace file {} line {}: no counts or timestamp",
+                            conf.path, lineno
+                        )
+                    });
+                let count = CSV_COUNT_COLS
+                    .iter()
+                    .find_map(|k| obj.get(*k))
+                    .map_or(1.0, as_count);
+                rows.push((key, ts, count));
+            }
+        }
+        Self::timestamp_rows_to_entries(conf, rows, &mut entries);
+        entries
+    }
+
+    // 时间戳以轨迹中最早的时间戳为第 0 帧
+    fn timestamp_rows_to_entries(
+        conf: &TraceConfig,
+        rows: Vec<(String, f64, f32)>,
+        entries: &mut Vec<TraceEntry>,
+    ) {
+        let Some(first_ts) = rows.iter().map(|r| r.1).min_by(|a, b| a.total_cmp(b)) else {
+            return;
+        };
+        for (key, ts, count) in rows {
+            entries.push(TraceEntry {
+                key,
+                begin_frame: ((ts - first_ts) / conf.secs_per_frame as f64) as usize,
+                frame_span: 1,
+                count,
+            });
+        }
+    }
+
+    // 每条记录对应的 dag：优先按 app_map 映射，其它应用按出现顺序依次映射
+    fn entry_dags(&self, dag_cnt: usize) -> Vec<DagId> {
+        let mut key_2_dag: HashMap<&str, DagId> = HashMap::new();
+        self.entries
+            .iter()
+            .map(|entry| {
+                let next_dag = key_2_dag.len() % dag_cnt;
+                let dag_i = *key_2_dag.entry(&entry.key).or_insert_with(|| {
+                    self.conf
+                        .app_map
+                        .get(&entry.key)
+                        .copied()
+                        .unwrap_or(next_dag)
+                });
+                assert!(dag_i < dag_cnt, "trace app {} mapped to invalid dag {}", entry.key, dag_i);
+                dag_i
+            })
+            .collect()
+    }
+
+    // 将轨迹展开为每一帧到达的请求
+    fn expand(&self, env: &SimEnv) -> BTreeMap<usize, Vec<DagId>> {
+        let mut frame_dags: BTreeMap<usize, Vec<DagId>> = BTreeMap::new();
+
+        let entry_dags = self.entry_dags(env.core.dags().len());
+        for (entry, dag_i) in self.entries.iter().zip(entry_dags) {
+            // 小数部分按概率取整
+            let scaled = entry.count * self.conf.scale;
+            let mut cnt = scaled.floor() as usize;
+            if env.workload_rand_f(0.0, 1.0) < scaled - scaled.floor() {
+                cnt += 1;
+            }
+            for _ in 0..cnt {
+                let frame = entry.begin_frame + env.workload_rand_i(0, entry.frame_span);
+                frame_dags.entry(frame).or_default().push(dag_i);
+            }
+        }
+        frame_dags
+    }
+}
+
+impl ReqGen for TraceReqGen {
+    fn gen_reqs(&mut self, env: &SimEnv) -> Vec<Request> {
+        if self.frame_dags.is_none() {
+            self.frame_dags = Some(self.expand(env));
+        }
+        self.frame_dags
+            .as_mut()
+            .unwrap()
+            .remove(&env.current_frame())
+            .unwrap_or_default()
+            .into_iter()
+            .map(|dag_i| Request::new(env, dag_i, env.current_frame()))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn conf(path: &str, extra: Value) -> TraceConfig {
+        let mut v = serde_json::json!({ "path": path });
+        v.as_object_mut()
+            .unwrap()
+            .extend(extra.as_object().unwrap().clone());
+        serde_json::from_value(v).unwrap()
+    }
+
+    fn entries_of(entries: &[TraceEntry]) -> Vec<(&str, usize, usize, f32)> {
+        entries
+            .iter()
+            .map(|e| (&*e.key, e.begin_frame, e.frame_span, e.count))
+            .collect()
+    }
+
+    #[test]
+    fn csv_per_minute() {
+        let conf = conf("t.csv", serde_json::json!({ "frames_per_minute": 10 }));
+        let content = "HashOwner,HashApp,HashFunction,Trigger,3,4,5\n\
+                       o1,a1,f1,http,1,0,2\n\
+                       \n\
+                       o1,a2,f2,timer,0,3,\n";
+        let entries = TraceReqGen::parse_csv(&conf, content);
+        assert_eq!(
+            entries_of(&entries),
+            vec![("a1", 0, 10, 1.0), ("a1", 20, 10, 2.0), ("a2", 10, 10, 3.0)]
+        );
+    }
+
+    #[test]
+    fn csv_per_minute_map_by_function() {
+        let conf = conf("t.csv", serde_json::json!({ "map_by": "function" }));
+        let content = "HashApp,HashFunction,1\na1,f1,1\na1,f2,1\n";
+        let entries = TraceReqGen::parse_csv(&conf, content);
+        assert_eq!(
+            entries_of(&entries),
+            vec![("f1", 0, 60, 1.0), ("f2", 0, 60, 1.0)]
+        );
+    }
+
+    #[test]
+    fn csv_timestamp() {
+        let conf = conf("t.csv", serde_json::json!({ "secs_per_frame": 0.5 }));
+        let content = "app,timestamp,count\na1,100.0,2\na2,101.2,1\na1,100.4,\n";
+        let entries = TraceReqGen::parse_csv(&conf, content);
+        assert_eq!(
+            entries_of(&entries),
+            vec![("a1", 0, 1, 2.0), ("a2", 2, 1, 1.0), ("a1", 0, 1, 0.0)]
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "t.csv line 3: missing column timestamp")]
+    fn csv_short_row() {
+        let conf = conf("t.csv", serde_json::json!({}));
+        TraceReqGen::parse_csv(&conf, "app,timestamp\na1,1\na2\n");
+    }
+
+    #[test]
+    #[should_panic(expected = "t.csv line 2: invalid count x")]
+    fn csv_invalid_count() {
+        let conf = conf("t.csv", serde_json::json!({}));
+        TraceReqGen::parse_csv(&conf, "app,timestamp,count\na1,1,x\n");
+    }
+
+    #[test]
+    #[should_panic(expected = "t.csv line 2: invalid timestamp ")]
+    fn csv_invalid_timestamp() {
+        let conf = conf("t.csv", serde_json::json!({}));
+        TraceReqGen::parse_csv(&conf, "app,timestamp\na1,\n");
+    }
+
+    #[test]
+    #[should_panic(expected = "t.csv line 2: quoted csv fields are not supported")]
+    fn csv_quoted_field() {
+        let conf = conf("t.csv", serde_json::json!({}));
+        TraceReqGen::parse_csv(&conf, "app,timestamp\n\"a,1\",1\n");
+    }
+
+    #[test]
+    fn jsonl_counts_and_timestamp() {
+        let conf = conf("t.jsonl", serde_json::json!({ "frames_per_minute": 2 }));
+        let content = "{\"app\": \"a1\", \"counts\": [0, 3, 1]}\n\
+                       {\"app\": 7, \"timestamp\": 10.0, \"count\": 2}\n\
+                       {\"app\": \"a2\", \"ts\": 12.0}\n";
+        let entries = TraceReqGen::parse_jsonl(&conf, content);
+        assert_eq!(
+            entries_of(&entries),
+            vec![
+                ("a1", 2, 2, 3.0),
+                ("a1", 4, 2, 1.0),
+                ("7", 0, 1, 2.0),
+                ("a2", 2, 1, 1.0)
+            ]
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "t.jsonl line 1: invalid count \"3\"")]
+    fn jsonl_invalid_count() {
+        let conf = conf("t.jsonl", serde_json::json!({}));
+        TraceReqGen::parse_jsonl(&conf, "{\"app\": \"a1\", \"counts\": [\"3\"]}\n");
+    }
+
+    #[test]
+    fn key_to_dag() {
+        let conf = conf("t.csv", serde_json::json!({ "app_map": { "b": 2 } }));
+        let content = "app,timestamp\na,1\nb,2\nc,3\na,4\nd,5\ne,6\n";
+        let gen = TraceReqGen {
+            entries: TraceReqGen::parse_csv(&conf, content),
+            conf,
+            frame_dags: None,
+        };
+        // b 按 app_map 映射，其它应用按出现顺序依次映射并对 dag 数取模
+        assert_eq!(gen.entry_dags(3), vec![0, 2, 2, 0, 0, 1]);
+    }
+}